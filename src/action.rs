@@ -1,12 +1,17 @@
+use std::path::PathBuf;
 use std::process::Command;
-use crate::util::Direction;
+use std::str::FromStr;
+use serde::{de, Deserialize, Deserializer};
+use crate::layout::{GapKind, LayoutKind};
+use crate::util::{expand_home, split_arguments, Direction};
 use crate::window_manager::WindowManager;
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub enum Action {
     Exit,
     ExecuteCommand {
         command: String,
+        args: Vec<String>,
     },
     MoveFocus {
         direction: Direction,
@@ -19,16 +24,50 @@ pub enum Action {
         direction: Direction,
         amount: f32,
     },
+    ChangeLayout {
+        layout: LayoutKind,
+    },
+    ChangeMasterCount {
+        delta: i32,
+    },
+    /// Sets a gap, or changes it by `amount` if `relative` is set.
+    ChangeGaps {
+        kind: GapKind,
+        amount: i32,
+        relative: bool,
+    },
+    SwitchWorkspace {
+        workspace: usize,
+    },
+    MoveFocusedWindowToWorkspace {
+        workspace: usize,
+    },
+    MoveFocusedWindowToOutput {
+        direction: Direction,
+    },
+    ToggleFloating,
+    ToggleFullscreen,
+    SaveLayout {
+        path: PathBuf,
+    },
+    LoadLayout {
+        path: PathBuf,
+    },
+    ReloadConfig,
+    Restart,
 }
 
 impl Action {
-    pub fn execute(&self, window_manager: &mut WindowManager) {
+    /// Runs the action. Failures that the caller should report are returned as an error message.
+    pub fn execute(&self, window_manager: &mut WindowManager) -> Result<(), String> {
         match self {
-            Action::Exit => unsafe {
+            Action::Exit => {
                 window_manager.exit();
             }
-            Action::ExecuteCommand { command } => {
-                Command::new(command).spawn().expect(format!("Couldn't execute command: '{}'", command).as_str());
+            Action::ExecuteCommand { command, args } => {
+                if let Err(error) = Command::new(command).args(args).spawn() {
+                    return Err(format!("Couldn't execute command: '{}': {}", command, error));
+                }
             }
             Action::MoveFocus { direction } => unsafe {
                 window_manager.move_focus(direction.clone());
@@ -40,8 +79,193 @@ impl Action {
                 window_manager.change_tiling_direction(direction.clone());
             }
             Action::ResizeFocusedWindow { direction, amount } => unsafe {
-                window_manager.resize_focused_window(direction.clone(), amount.clone());
+                window_manager.resize_focused_window(direction.clone(), *amount);
+            }
+            Action::ChangeLayout { layout } => unsafe {
+                window_manager.change_layout(layout.clone());
+            }
+            Action::ChangeMasterCount { delta } => unsafe {
+                window_manager.change_master_count(*delta);
+            }
+            Action::ChangeGaps { kind, amount, relative } => unsafe {
+                window_manager.change_gaps(*kind, *amount, *relative);
+            }
+            Action::SwitchWorkspace { workspace } => unsafe {
+                window_manager.switch_workspace(*workspace);
+            }
+            Action::MoveFocusedWindowToWorkspace { workspace } => unsafe {
+                window_manager.move_focused_window_to_workspace(*workspace);
+            }
+            Action::MoveFocusedWindowToOutput { direction } => unsafe {
+                window_manager.move_focused_window_to_output(direction.clone());
+            }
+            Action::ToggleFloating => unsafe {
+                window_manager.toggle_floating();
+            }
+            Action::ToggleFullscreen => unsafe {
+                window_manager.toggle_fullscreen();
+            }
+            Action::SaveLayout { path } => unsafe {
+                window_manager.save_layout(path).map_err(|error| format!("Couldn't save layout to {}: {}", path.display(), error))?;
+            }
+            Action::LoadLayout { path } => unsafe {
+                window_manager.load_layout(path).map_err(|error| format!("Couldn't load layout from {}: {}", path.display(), error))?;
+            }
+            Action::ReloadConfig => unsafe {
+                window_manager.reload_config()?;
+            }
+            Action::Restart => {
+                window_manager.restart()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses commands like `focus left`, `resize right 0.05` or `exec alacritty -e htop`. Workspaces are
+/// numbered starting at 1.
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = split_arguments(s)?;
+        let Some((name, args)) = words.split_first() else {
+            return Err("empty command".to_string());
+        };
+        let expect_args = |count: usize| {
+            if args.len() == count {
+                Ok(())
+            } else {
+                Err(format!("'{}' expects {} argument(s), got {}", name, count, args.len()))
+            }
+        };
+        let action = match name.as_str() {
+            "exit" => {
+                expect_args(0)?;
+                Action::Exit
+            }
+            "exec" => {
+                let Some((command, args)) = args.split_first() else {
+                    return Err("'exec' expects a command".to_string());
+                };
+                Action::ExecuteCommand { command: command.clone(), args: args.to_vec() }
             }
+            "focus" => {
+                expect_args(1)?;
+                Action::MoveFocus { direction: args[0].parse()? }
+            }
+            "close" => {
+                expect_args(0)?;
+                Action::CloseFocusedWindow
+            }
+            "split" => {
+                expect_args(1)?;
+                Action::ChangeTilingDirection { direction: args[0].parse()? }
+            }
+            "resize" => {
+                expect_args(2)?;
+                let amount = args[1].parse().map_err(|_| format!("invalid amount '{}'", args[1]))?;
+                Action::ResizeFocusedWindow { direction: args[0].parse()?, amount }
+            }
+            "layout" => {
+                expect_args(1)?;
+                Action::ChangeLayout { layout: args[0].parse()? }
+            }
+            "master-count" => {
+                expect_args(1)?;
+                let delta = args[0].parse().map_err(|_| format!("invalid master count change '{}'", args[0]))?;
+                Action::ChangeMasterCount { delta }
+            }
+            "gaps" => {
+                expect_args(2)?;
+                let kind = match args[0].as_str() {
+                    "inner" => GapKind::Inner,
+                    "outer" => GapKind::Outer,
+                    other => return Err(format!("unknown gap '{}', expected inner or outer", other)),
+                };
+                let amount = args[1].parse().map_err(|_| format!("invalid gap size '{}'", args[1]))?;
+                let relative = args[1].starts_with(['+', '-']);
+                Action::ChangeGaps { kind, amount, relative }
+            }
+            "workspace" => {
+                expect_args(1)?;
+                Action::SwitchWorkspace { workspace: parse_workspace(&args[0])? }
+            }
+            "move-to-workspace" => {
+                expect_args(1)?;
+                Action::MoveFocusedWindowToWorkspace { workspace: parse_workspace(&args[0])? }
+            }
+            "move-to-output" => {
+                expect_args(1)?;
+                Action::MoveFocusedWindowToOutput { direction: args[0].parse()? }
+            }
+            "toggle-floating" => {
+                expect_args(0)?;
+                Action::ToggleFloating
+            }
+            "toggle-fullscreen" => {
+                expect_args(0)?;
+                Action::ToggleFullscreen
+            }
+            "save-layout" => {
+                expect_args(1)?;
+                Action::SaveLayout { path: expand_home(&args[0]) }
+            }
+            "load-layout" => {
+                expect_args(1)?;
+                Action::LoadLayout { path: expand_home(&args[0]) }
+            }
+            "reload" => {
+                expect_args(0)?;
+                Action::ReloadConfig
+            }
+            "restart" => {
+                expect_args(0)?;
+                Action::Restart
+            }
+            _ => return Err(format!("unknown command '{}'", name)),
+        };
+        Ok(action)
+    }
+}
+
+impl<'de> Deserialize<'de> for Action {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let command = String::deserialize(deserializer)?;
+        command.parse().map_err(de::Error::custom)
+    }
+}
+
+fn parse_workspace(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(number) if number > 0 => Ok(number - 1),
+        _ => Err(format!("invalid workspace '{}', workspaces are numbered starting at 1", s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        assert!(matches!("focus left".parse(), Ok(Action::MoveFocus { direction: Direction::Left })));
+        assert!(matches!("resize right 0.05".parse(), Ok(Action::ResizeFocusedWindow { direction: Direction::Right, amount }) if amount == 0.05));
+        assert!(matches!("workspace 2".parse(), Ok(Action::SwitchWorkspace { workspace: 1 })));
+        assert!(matches!("reload".parse(), Ok(Action::ReloadConfig)));
+        assert!(matches!("gaps inner +4".parse(), Ok(Action::ChangeGaps { kind: GapKind::Inner, amount: 4, relative: true })));
+        assert!(matches!("gaps outer 0".parse(), Ok(Action::ChangeGaps { kind: GapKind::Outer, amount: 0, relative: false })));
+        let Ok(Action::ExecuteCommand { command, args }) = "exec alacritty -e 'htop -d 5'".parse() else {
+            panic!("exec should parse");
+        };
+        assert_eq!(command, "alacritty");
+        assert_eq!(args, ["-e", "htop -d 5"]);
+    }
+
+    #[test]
+    fn rejects_invalid_commands() {
+        for command in ["", "teleport", "focus", "focus sideways", "close now", "reload now", "workspace 0", "resize left much", "exec 'alacritty", "gaps middle 4"] {
+            assert!(command.parse::<Action>().is_err(), "'{}' should be rejected", command);
         }
     }
 }
@@ -0,0 +1,99 @@
+use std::ffi::{c_ulong, CStr, CString};
+use x11::xlib::{Display, False, XDefaultScreen, XInternAtom};
+
+pub struct Atoms {
+    /// `WM_S<n>`, the ICCCM selection owned by the window manager of screen `n`.
+    pub wm_selection: c_ulong,
+    pub manager: c_ulong,
+    pub wm_protocols: c_ulong,
+    pub wm_delete_window: c_ulong,
+    pub wm_window_role: c_ulong,
+    pub utf8_string: c_ulong,
+    pub net_supported: c_ulong,
+    pub net_supporting_wm_check: c_ulong,
+    pub net_wm_name: c_ulong,
+    pub net_client_list: c_ulong,
+    pub net_client_list_stacking: c_ulong,
+    pub net_active_window: c_ulong,
+    pub net_number_of_desktops: c_ulong,
+    pub net_current_desktop: c_ulong,
+    pub net_wm_desktop: c_ulong,
+    pub net_close_window: c_ulong,
+    pub net_wm_state: c_ulong,
+    pub net_wm_state_demands_attention: c_ulong,
+    pub net_wm_state_fullscreen: c_ulong,
+    pub net_wm_window_type: c_ulong,
+    pub net_wm_window_type_dock: c_ulong,
+    pub net_wm_window_type_dialog: c_ulong,
+    pub net_wm_window_type_utility: c_ulong,
+    pub net_wm_window_type_splash: c_ulong,
+    pub net_wm_window_type_notification: c_ulong,
+    pub net_wm_strut: c_ulong,
+    pub net_wm_strut_partial: c_ulong,
+}
+
+impl Atoms {
+    pub unsafe fn new(display: *mut Display) -> Self {
+        let wm_selection = CString::new(format!("WM_S{}", XDefaultScreen(display))).unwrap();
+        Self {
+            wm_selection: intern(display, &wm_selection),
+            manager: intern(display, c"MANAGER"),
+            wm_protocols: intern(display, c"WM_PROTOCOLS"),
+            wm_delete_window: intern(display, c"WM_DELETE_WINDOW"),
+            wm_window_role: intern(display, c"WM_WINDOW_ROLE"),
+            utf8_string: intern(display, c"UTF8_STRING"),
+            net_supported: intern(display, c"_NET_SUPPORTED"),
+            net_supporting_wm_check: intern(display, c"_NET_SUPPORTING_WM_CHECK"),
+            net_wm_name: intern(display, c"_NET_WM_NAME"),
+            net_client_list: intern(display, c"_NET_CLIENT_LIST"),
+            net_client_list_stacking: intern(display, c"_NET_CLIENT_LIST_STACKING"),
+            net_active_window: intern(display, c"_NET_ACTIVE_WINDOW"),
+            net_number_of_desktops: intern(display, c"_NET_NUMBER_OF_DESKTOPS"),
+            net_current_desktop: intern(display, c"_NET_CURRENT_DESKTOP"),
+            net_wm_desktop: intern(display, c"_NET_WM_DESKTOP"),
+            net_close_window: intern(display, c"_NET_CLOSE_WINDOW"),
+            net_wm_state: intern(display, c"_NET_WM_STATE"),
+            net_wm_state_demands_attention: intern(display, c"_NET_WM_STATE_DEMANDS_ATTENTION"),
+            net_wm_state_fullscreen: intern(display, c"_NET_WM_STATE_FULLSCREEN"),
+            net_wm_window_type: intern(display, c"_NET_WM_WINDOW_TYPE"),
+            net_wm_window_type_dock: intern(display, c"_NET_WM_WINDOW_TYPE_DOCK"),
+            net_wm_window_type_dialog: intern(display, c"_NET_WM_WINDOW_TYPE_DIALOG"),
+            net_wm_window_type_utility: intern(display, c"_NET_WM_WINDOW_TYPE_UTILITY"),
+            net_wm_window_type_splash: intern(display, c"_NET_WM_WINDOW_TYPE_SPLASH"),
+            net_wm_window_type_notification: intern(display, c"_NET_WM_WINDOW_TYPE_NOTIFICATION"),
+            net_wm_strut: intern(display, c"_NET_WM_STRUT"),
+            net_wm_strut_partial: intern(display, c"_NET_WM_STRUT_PARTIAL"),
+        }
+    }
+
+    /// The EWMH hints that are advertised in `_NET_SUPPORTED`.
+    pub fn supported(&self) -> Vec<c_ulong> {
+        vec![
+            self.net_supported,
+            self.net_supporting_wm_check,
+            self.net_wm_name,
+            self.net_client_list,
+            self.net_client_list_stacking,
+            self.net_active_window,
+            self.net_number_of_desktops,
+            self.net_current_desktop,
+            self.net_wm_desktop,
+            self.net_close_window,
+            self.net_wm_state,
+            self.net_wm_state_demands_attention,
+            self.net_wm_state_fullscreen,
+            self.net_wm_window_type,
+            self.net_wm_window_type_dock,
+            self.net_wm_window_type_dialog,
+            self.net_wm_window_type_utility,
+            self.net_wm_window_type_splash,
+            self.net_wm_window_type_notification,
+            self.net_wm_strut,
+            self.net_wm_strut_partial,
+        ]
+    }
+}
+
+unsafe fn intern(display: *mut Display, name: &CStr) -> c_ulong {
+    XInternAtom(display, name.as_ptr(), False)
+}
@@ -0,0 +1,63 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::process::ExitCode;
+use serde_json::Value;
+use xnebula::ipc::socket_path;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        eprintln!("Usage: xnebula-msg <command> [arguments...]");
+        return ExitCode::FAILURE;
+    }
+    let command = args.iter().map(|arg| quote(arg)).collect::<Vec<_>>().join(" ");
+
+    let Some(path) = socket_path() else {
+        eprintln!("Couldn't find the IPC socket, neither XNEBULA_SOCKET nor XDG_RUNTIME_DIR is set");
+        return ExitCode::FAILURE;
+    };
+    let mut stream = match UnixStream::connect(&path) {
+        Ok(stream) => stream,
+        Err(error) => {
+            eprintln!("Couldn't connect to {}: {}", path.display(), error);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(error) = writeln!(stream, "{}", command) {
+        eprintln!("Couldn't send command: {}", error);
+        return ExitCode::FAILURE;
+    }
+
+    let mut reader = BufReader::new(&stream);
+    let mut reply = String::new();
+    if let Err(error) = reader.read_line(&mut reply) {
+        eprintln!("Couldn't read reply: {}", error);
+        return ExitCode::FAILURE;
+    }
+    print!("{}", reply);
+    let success = serde_json::from_str::<Value>(&reply)
+        .ok()
+        .and_then(|reply| reply.get("success").and_then(Value::as_bool))
+        .unwrap_or(false);
+    if success && args[0] == "subscribe" {
+        // Events keep coming until the window manager closes the connection.
+        for event in reader.lines() {
+            match event {
+                Ok(event) => println!("{}", event),
+                Err(_) => break,
+            }
+        }
+    }
+    if success { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
+
+/// Quotes an argument so that the window manager reads it back as a single word.
+fn quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'') {
+        arg.to_string()
+    } else if !arg.contains('\'') {
+        format!("'{}'", arg)
+    } else {
+        format!("\"{}\"", arg)
+    }
+}
@@ -0,0 +1,37 @@
+use std::ffi::c_int;
+use std::time::Instant;
+use crate::util::Bounds;
+
+#[derive(Debug)]
+pub struct Client {
+    pub workspace: usize,
+    /// The border width the window had before it was managed, restored when the window manager exits.
+    pub original_border_width: c_int,
+    /// Border width set by a rule, overriding the configured one.
+    pub border_width: Option<c_int>,
+    /// Number of UnmapNotify events caused by the window manager itself that are still to be received.
+    pub pending_unmaps: u32,
+    /// When the client gets killed if it doesn't react to WM_DELETE_WINDOW.
+    pub kill_deadline: Option<Instant>,
+    /// Geometry of the window while it is floating, `None` if it is tiled.
+    pub floating: Option<Bounds>,
+    /// Fullscreen windows cover their output but keep their place in the layout.
+    pub fullscreen: bool,
+    /// Set through `_NET_WM_STATE_DEMANDS_ATTENTION`, cleared when the window gets focused.
+    pub urgent: bool,
+}
+
+impl Client {
+    pub fn new(workspace: usize) -> Self {
+        Self {
+            workspace,
+            original_border_width: 0,
+            border_width: None,
+            pending_unmaps: 0,
+            kill_deadline: None,
+            floating: None,
+            fullscreen: false,
+            urgent: false,
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::ffi::{c_char, c_ulong};
+use x11::xlib::{Display, DoBlue, DoGreen, DoRed, XAllocColor, XColor, XDefaultColormap, XDefaultScreen, XFreeColors, XWhitePixel};
+use crate::config::{Border, Color};
+
+/// The pixel values of the border colors, allocated in the default colormap of the screen.
+#[derive(Debug)]
+pub struct BorderColors {
+    pub focused: c_ulong,
+    pub unfocused: c_ulong,
+    pub urgent: c_ulong,
+}
+
+impl BorderColors {
+    pub unsafe fn allocate(display: *mut Display, border: &Border) -> Self {
+        Self {
+            focused: allocate_color(display, border.focused),
+            unfocused: allocate_color(display, border.unfocused),
+            urgent: allocate_color(display, border.urgent),
+        }
+    }
+
+    pub unsafe fn free(&self, display: *mut Display) {
+        let screen = XDefaultScreen(display);
+        let mut pixels = [self.focused, self.unfocused, self.urgent];
+        XFreeColors(display, XDefaultColormap(display, screen), pixels.as_mut_ptr(), pixels.len() as _, 0);
+    }
+}
+
+/// Returns the pixel value closest to `color` that the screen can show. Falls back to white if the
+/// colormap is full.
+unsafe fn allocate_color(display: *mut Display, color: Color) -> c_ulong {
+    let screen = XDefaultScreen(display);
+    let mut xcolor = XColor {
+        pixel: 0,
+        red: color.red as u16 * 0x101,
+        green: color.green as u16 * 0x101,
+        blue: color.blue as u16 * 0x101,
+        flags: (DoRed | DoGreen | DoBlue) as c_char,
+        pad: 0,
+    };
+    if XAllocColor(display, XDefaultColormap(display, screen), &mut xcolor) == 0 {
+        eprintln!("Couldn't allocate color {:?}", color);
+        return XWhitePixel(display, screen);
+    }
+    xcolor.pixel
+}
@@ -1,39 +1,117 @@
-use std::ffi::{c_int, c_ulong};
-use crate::util::Bounds;
+use std::collections::HashMap;
+use std::ffi::{c_int, c_uint};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use x11::keysym::{XK_1, XK_d, XK_Down, XK_e, XK_f, XK_i, XK_Left, XK_m, XK_q, XK_r, XK_Return, XK_Right, XK_space, XK_t, XK_Up};
+use x11::xlib::{ControlMask, Mod1Mask, Mod4Mask, ShiftMask};
+use crate::action::Action;
+use crate::keybind::KeyCombination;
+use crate::layout::{Gaps, LayoutKind};
+use crate::rules::Rule;
+use crate::util::{Bounds, Direction};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Monitor {
+    #[serde(flatten)]
     pub bounds: Bounds,
 }
 
-impl Monitor {
-    pub fn new(width: c_int, height: c_int, x: c_int, y: c_int) -> Self {
+/// A color written as `#rrggbb`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl Color {
+    pub const fn new(red: u8, green: u8, blue: u8) -> Self {
         Self {
-            bounds: Bounds::new(x, y, width, height),
+            red,
+            green,
+            blue,
         }
     }
 }
 
-#[derive(Debug)]
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid color '{}', expected #rrggbb", s);
+        let hex = s.strip_prefix('#').filter(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit())).ok_or_else(invalid)?;
+        let component = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).map_err(|_| invalid());
+        Ok(Self::new(component(0)?, component(2)?, component(4)?))
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let color = String::deserialize(deserializer)?;
+        color.parse().map_err(de::Error::custom)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Border {
     pub width: c_int,
-    pub color: c_ulong,
+    /// Color of the border of the focused window.
+    pub focused: Color,
+    pub unfocused: Color,
+    /// Color of the border of windows that demand attention.
+    pub urgent: Color,
 }
 
 impl Default for Border {
     fn default() -> Self {
         Self {
             width: 3,
-            color: 0x00ffffff,
+            focused: Color::new(0xff, 0xff, 0xff),
+            unfocused: Color::new(0x44, 0x44, 0x44),
+            urgent: Color::new(0xe0, 0x40, 0x40),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MasterStackConfig {
+    pub master_count: usize,
+    /// The share of the width taken by the masters, between 0.01 and 0.99.
+    #[serde(deserialize_with = "deserialize_master_ratio")]
+    pub master_ratio: f32,
+}
+
+impl Default for MasterStackConfig {
+    fn default() -> Self {
+        Self {
+            master_count: 1,
+            master_ratio: 0.55,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     pub debug_events: bool,
     pub monitors: Vec<Monitor>,
     pub border: Option<Border>,
+    pub gaps: Gaps,
+    pub layout: LayoutKind,
+    pub master_stack: MasterStackConfig,
+    pub workspace_count: usize,
+    /// How long a window gets to react to WM_DELETE_WINDOW before its client is killed. Written in
+    /// seconds, 0 disables killing.
+    #[serde(deserialize_with = "deserialize_timeout")]
+    pub kill_timeout: Option<Duration>,
+    pub keybinds: HashMap<KeyCombination, Action>,
+    /// Applied in order to every window that gets mapped.
+    pub rules: Vec<Rule>,
 }
 
 impl Default for Config {
@@ -42,6 +120,184 @@ impl Default for Config {
             debug_events: false,
             monitors: Vec::new(),
             border: Some(Border::default()),
+            gaps: Gaps::default(),
+            layout: LayoutKind::Tree,
+            master_stack: MasterStackConfig::default(),
+            workspace_count: 9,
+            kill_timeout: Some(Duration::from_secs(5)),
+            keybinds: default_keybinds(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Returns `$XDG_CONFIG_HOME/xnebula/config.toml`, falling back to `~/.config` if `XDG_CONFIG_HOME`
+    /// isn't set.
+    pub fn path() -> Option<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(config_home.join("xnebula").join("config.toml"))
+    }
+
+    /// Loads the config file, or the default config if there is none.
+    pub fn load() -> Result<Self, ConfigError> {
+        match Self::path() {
+            Some(path) if path.exists() => Self::load_from(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, ConfigError> {
+        let source = std::fs::read_to_string(path).map_err(|error| ConfigError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        Self::parse(&source, path)
+    }
+
+    /// Parses the contents of the config file at `path`, which is only used for error messages.
+    fn parse(source: &str, path: &Path) -> Result<Self, ConfigError> {
+        toml::from_str(source).map_err(|error| {
+            let (line, column) = match error.span() {
+                Some(span) => line_and_column(source, span.start),
+                None => (1, 1),
+            };
+            ConfigError::Parse {
+                path: path.to_path_buf(),
+                line,
+                column,
+                message: error.message().to_string(),
+            }
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io { path, error } => write!(f, "Couldn't read config file {}: {}", path.display(), error),
+            ConfigError::Parse { path, line, column, message } => write!(f, "Error in config file {}:{}:{}: {}", path.display(), line, column, message),
+        }
+    }
+}
+
+/// Converts a byte offset into a 1-based line and column.
+fn line_and_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+    (line, column)
+}
+
+fn deserialize_timeout<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    let seconds = f64::deserialize(deserializer)?;
+    if seconds > 0.0 {
+        Duration::try_from_secs_f64(seconds).map(Some).map_err(de::Error::custom)
+    } else {
+        Ok(None)
+    }
+}
+
+fn deserialize_master_ratio<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    let ratio = f32::deserialize(deserializer)?;
+    if (0.01..=0.99).contains(&ratio) {
+        Ok(ratio)
+    } else {
+        Err(de::Error::custom(format!("invalid master_ratio {}, expected a value between 0.01 and 0.99", ratio)))
+    }
+}
+
+fn default_keybinds() -> HashMap<KeyCombination, Action> {
+    let mut keybinds = HashMap::new();
+    let mut bind = |keysym: c_uint, modifiers: c_uint, action: Action| {
+        keybinds.insert(KeyCombination::new(keysym, modifiers), action);
+    };
+    bind(XK_e, Mod4Mask, Action::Exit);
+    bind(XK_Return, Mod4Mask, Action::ExecuteCommand { command: "alacritty".to_string(), args: Vec::new() });
+    bind(XK_q, Mod4Mask | ShiftMask, Action::CloseFocusedWindow);
+    for (keysym, direction) in [(XK_Left, Direction::Left), (XK_Right, Direction::Right), (XK_Up, Direction::Up), (XK_Down, Direction::Down)] {
+        bind(keysym, Mod4Mask, Action::MoveFocus { direction: direction.clone() });
+        bind(keysym, Mod4Mask | Mod1Mask, Action::ChangeTilingDirection { direction: direction.clone() });
+        bind(keysym, Mod4Mask | ControlMask, Action::ResizeFocusedWindow { direction: direction.clone(), amount: 0.1 });
+        bind(keysym, Mod4Mask | ShiftMask, Action::MoveFocusedWindowToOutput { direction });
+    }
+    bind(XK_t, Mod4Mask, Action::ChangeLayout { layout: LayoutKind::Tree });
+    bind(XK_m, Mod4Mask, Action::ChangeLayout { layout: LayoutKind::MasterStack });
+    bind(XK_i, Mod4Mask, Action::ChangeMasterCount { delta: 1 });
+    bind(XK_d, Mod4Mask, Action::ChangeMasterCount { delta: -1 });
+    for workspace in 0..9 {
+        bind(XK_1 + workspace as c_uint, Mod4Mask, Action::SwitchWorkspace { workspace });
+        bind(XK_1 + workspace as c_uint, Mod4Mask | ShiftMask, Action::MoveFocusedWindowToWorkspace { workspace });
+    }
+    bind(XK_space, Mod4Mask | ShiftMask, Action::ToggleFloating);
+    bind(XK_f, Mod4Mask, Action::ToggleFullscreen);
+    bind(XK_r, Mod4Mask | ShiftMask, Action::ReloadConfig);
+    bind(XK_r, Mod4Mask | ControlMask, Action::Restart);
+    keybinds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keybinds_in_the_config_replace_the_default_ones() {
+        let config: Config = toml::from_str("layout = \"master-stack\"\n[keybinds]\n\"Mod4+Return\" = \"exec xterm -e htop\"\n").unwrap();
+        assert_eq!(config.layout, LayoutKind::MasterStack);
+        assert_eq!(config.keybinds.len(), 1);
+        let action = &config.keybinds[&KeyCombination::new(XK_Return, Mod4Mask)];
+        assert!(matches!(action, Action::ExecuteCommand { command, args } if command == "xterm" && args == &["-e", "htop"]));
+    }
+
+    #[test]
+    fn parses_colors() {
+        assert_eq!("#e04040".parse(), Ok(Color::new(0xe0, 0x40, 0x40)));
+        assert_eq!("#FFFFFF".parse(), Ok(Color::new(0xff, 0xff, 0xff)));
+        for color in ["e04040", "#e0404", "#e0404000", "#e0g040", "#+10203", ""] {
+            assert!(color.parse::<Color>().is_err(), "'{}' should be rejected", color);
+        }
+        let config: Config = toml::from_str("[border]\nurgent = \"#102030\"\n").unwrap();
+        assert_eq!(config.border.unwrap().urgent, Color::new(0x10, 0x20, 0x30));
+    }
+
+    #[test]
+    fn master_ratios_outside_of_the_layout_are_rejected() {
+        let config: Config = toml::from_str("[master_stack]\nmaster_ratio = 0.7\n").unwrap();
+        assert_eq!(config.master_stack.master_ratio, 0.7);
+        for ratio in ["0", "-0.5", "1.5", "nan", "inf"] {
+            let source = format!("[master_stack]\nmaster_ratio = {}\n", ratio);
+            assert!(toml::from_str::<Config>(&source).is_err(), "master_ratio = {} should be rejected", ratio);
+        }
+    }
+
+    #[test]
+    fn kill_timeouts_that_dont_fit_a_duration_are_rejected() {
+        let parse = |source: &str| Config::parse(source, Path::new("config.toml"));
+        assert_eq!(parse("kill_timeout = 1.5").unwrap().kill_timeout, Some(Duration::from_millis(1500)));
+        assert_eq!(parse("kill_timeout = 0").unwrap().kill_timeout, None);
+        for timeout in ["inf", "1e300"] {
+            let source = format!("workspace_count = 4\nkill_timeout = {}\n", timeout);
+            let Err(ConfigError::Parse { line, column, .. }) = parse(&source) else {
+                panic!("kill_timeout = {} should be rejected", timeout);
+            };
+            assert_eq!((line, column), (2, 16));
         }
     }
 }
@@ -0,0 +1,118 @@
+use std::ffi::{c_ulong, CStr};
+use serde::{Deserialize, Serialize};
+use x11::xlib::{AnyPropertyType, Display, XClassHint, XFetchName, XFree, XGetClassHint};
+use crate::atoms::Atoms;
+use crate::ewmh;
+
+/// The properties of a window that criteria are matched against.
+#[derive(Debug, Default)]
+pub struct WindowProperties {
+    /// The class part of `WM_CLASS`.
+    pub class: String,
+    /// The instance part of `WM_CLASS`.
+    pub instance: String,
+    /// `WM_WINDOW_ROLE`, which tells apart the different windows of an application.
+    pub role: String,
+    /// `_NET_WM_NAME`, or `WM_NAME` if the window doesn't set it.
+    pub title: String,
+}
+
+impl WindowProperties {
+    pub unsafe fn read(display: *mut Display, window: c_ulong, atoms: &Atoms) -> Self {
+        let mut properties = Self::default();
+
+        let mut class_hint = XClassHint {
+            res_name: std::ptr::null_mut(),
+            res_class: std::ptr::null_mut(),
+        };
+        if XGetClassHint(display, window, &mut class_hint) != 0 {
+            if !class_hint.res_name.is_null() {
+                properties.instance = CStr::from_ptr(class_hint.res_name).to_string_lossy().into_owned();
+                XFree(class_hint.res_name as *mut _);
+            }
+            if !class_hint.res_class.is_null() {
+                properties.class = CStr::from_ptr(class_hint.res_class).to_string_lossy().into_owned();
+                XFree(class_hint.res_class as *mut _);
+            }
+        }
+
+        properties.role = ewmh::get_string(display, window, atoms.wm_window_role, AnyPropertyType as c_ulong).unwrap_or_default();
+        properties.title = match ewmh::get_string(display, window, atoms.net_wm_name, atoms.utf8_string) {
+            Some(title) => title,
+            None => {
+                let mut name = std::ptr::null_mut();
+                if XFetchName(display, window, &mut name) != 0 && !name.is_null() {
+                    let title = CStr::from_ptr(name).to_string_lossy().into_owned();
+                    XFree(name as *mut _);
+                    title
+                } else {
+                    String::new()
+                }
+            }
+        };
+        properties
+    }
+}
+
+/// Describes which windows something applies to. Every property that is set has to match exactly.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Criteria {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+impl Criteria {
+    /// Criteria that match windows of the same application as the one with these properties. Windows
+    /// without `WM_CLASS` are matched by their title instead. Returns `None` if the window has neither,
+    /// since criteria without properties would match every window.
+    pub fn from_properties(properties: &WindowProperties) -> Option<Self> {
+        let non_empty = |value: &str| Some(value.to_string()).filter(|value| !value.is_empty());
+        let class = non_empty(&properties.class);
+        let instance = non_empty(&properties.instance);
+        let title = if class.is_none() && instance.is_none() { non_empty(&properties.title) } else { None };
+        if class.is_none() && instance.is_none() && title.is_none() {
+            return None;
+        }
+        Some(Self { class, instance, title })
+    }
+
+    pub fn matches(&self, properties: &WindowProperties) -> bool {
+        let matches = |expected: &Option<String>, actual: &str| expected.as_ref().is_none_or(|expected| expected == actual);
+        matches(&self.class, &properties.class) && matches(&self.instance, &properties.instance) && matches(&self.title, &properties.title)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn criteria_match_the_properties_that_are_set() {
+        let properties = WindowProperties { class: "Emacs".to_string(), title: "notes.org".to_string(), ..Default::default() };
+        let criteria = Criteria::from_properties(&properties).unwrap();
+        assert_eq!(criteria, Criteria { class: Some("Emacs".to_string()), ..Default::default() });
+        assert!(criteria.matches(&WindowProperties { title: "todo.org".to_string(), ..properties }));
+        assert!(!criteria.matches(&WindowProperties { class: "emacs".to_string(), ..Default::default() }));
+    }
+
+    #[test]
+    fn criteria_fall_back_to_the_title() {
+        let mut properties = WindowProperties { class: "Emacs".to_string(), title: "notes.org".to_string(), ..Default::default() };
+        let criteria = Criteria::from_properties(&properties).unwrap();
+        assert_eq!(criteria, Criteria { class: Some("Emacs".to_string()), ..Default::default() });
+
+        properties.class.clear();
+        let criteria = Criteria::from_properties(&properties).unwrap();
+        assert_eq!(criteria, Criteria { title: Some("notes.org".to_string()), ..Default::default() });
+        assert!(criteria.matches(&properties));
+        assert!(!criteria.matches(&WindowProperties::default()));
+
+        properties.title.clear();
+        assert_eq!(Criteria::from_properties(&properties), None);
+    }
+}
@@ -0,0 +1,176 @@
+use std::ffi::{c_int, c_long, c_uchar, c_ulong};
+use x11::xlib::{AnyPropertyType, Display, False, PropModeReplace, Success, XChangeProperty, XCreateSimpleWindow, XDeleteProperty, XFree, XGetWindowProperty, XA_ATOM, XA_CARDINAL, XA_WINDOW};
+use crate::atoms::Atoms;
+use crate::util::Bounds;
+
+/// Actions of a `_NET_WM_STATE` client message.
+pub const STATE_REMOVE: c_long = 0;
+pub const STATE_ADD: c_long = 1;
+pub const STATE_TOGGLE: c_long = 2;
+
+/// Space that a dock reserves at the edges of the screen, see `_NET_WM_STRUT_PARTIAL`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Strut {
+    pub left: c_ulong,
+    pub right: c_ulong,
+    pub top: c_ulong,
+    pub bottom: c_ulong,
+    pub left_start_y: c_ulong,
+    pub left_end_y: c_ulong,
+    pub right_start_y: c_ulong,
+    pub right_end_y: c_ulong,
+    pub top_start_x: c_ulong,
+    pub top_end_x: c_ulong,
+    pub bottom_start_x: c_ulong,
+    pub bottom_end_x: c_ulong,
+}
+
+impl Strut {
+    /// Reads `_NET_WM_STRUT_PARTIAL`, or the older `_NET_WM_STRUT` which always spans a whole edge.
+    pub unsafe fn read(display: *mut Display, window: c_ulong, atoms: &Atoms) -> Option<Self> {
+        let values = get_longs(display, window, atoms.net_wm_strut_partial);
+        if let [left, right, top, bottom, left_start_y, left_end_y, right_start_y, right_end_y, top_start_x, top_end_x, bottom_start_x, bottom_end_x] = values[..] {
+            return Some(Self { left, right, top, bottom, left_start_y, left_end_y, right_start_y, right_end_y, top_start_x, top_end_x, bottom_start_x, bottom_end_x });
+        }
+        let values = get_longs(display, window, atoms.net_wm_strut);
+        if let [left, right, top, bottom] = values[..] {
+            let max = c_ulong::MAX;
+            return Some(Self { left, right, top, bottom, left_start_y: 0, left_end_y: max, right_start_y: 0, right_end_y: max, top_start_x: 0, top_end_x: max, bottom_start_x: 0, bottom_end_x: max });
+        }
+        None
+    }
+
+    /// Cuts the reserved space out of `bounds`. Struts are measured from the edges of the screen, which is
+    /// `screen_width` by `screen_height` pixels large.
+    pub fn apply(&self, bounds: &Bounds, screen_width: i32, screen_height: i32) -> Bounds {
+        let overlaps = |start: c_ulong, end: c_ulong, from: i32, length: i32| {
+            // The ends can be as large as `c_ulong::MAX`, which doesn't fit into an i64
+            (start as i128) < (from + length) as i128 && from as i128 <= end as i128
+        };
+        let (mut left, mut top) = (bounds.x, bounds.y);
+        let (mut right, mut bottom) = (bounds.x + bounds.width, bounds.y + bounds.height);
+        if self.left > 0 && overlaps(self.left_start_y, self.left_end_y, bounds.y, bounds.height) {
+            left = left.max(self.left as i32);
+        }
+        if self.right > 0 && overlaps(self.right_start_y, self.right_end_y, bounds.y, bounds.height) {
+            right = right.min(screen_width - self.right as i32);
+        }
+        if self.top > 0 && overlaps(self.top_start_x, self.top_end_x, bounds.x, bounds.width) {
+            top = top.max(self.top as i32);
+        }
+        if self.bottom > 0 && overlaps(self.bottom_start_x, self.bottom_end_x, bounds.x, bounds.width) {
+            bottom = bottom.min(screen_height - self.bottom as i32);
+        }
+        if right <= left || bottom <= top {
+            return bounds.clone();
+        }
+        Bounds::new(left, top, right - left, bottom - top)
+    }
+}
+
+/// Creates the window that proves to other clients that an EWMH compliant window manager is running. It
+/// also owns the `WM_S<n>` selection.
+pub unsafe fn create_check_window(display: *mut Display, root_window: c_ulong) -> c_ulong {
+    XCreateSimpleWindow(display, root_window, -1, -1, 1, 1, 0, 0, 0)
+}
+
+/// Advertises the check window and the supported hints on the root window.
+pub unsafe fn init(display: *mut Display, root_window: c_ulong, check_window: c_ulong, atoms: &Atoms) {
+    set_window(display, check_window, atoms.net_supporting_wm_check, check_window);
+    set_utf8_string(display, check_window, atoms.utf8_string, atoms.net_wm_name, "xnebula");
+    set_window(display, root_window, atoms.net_supporting_wm_check, check_window);
+    set_atoms(display, root_window, atoms.net_supported, &atoms.supported());
+}
+
+pub unsafe fn set_cardinal(display: *mut Display, window: c_ulong, property: c_ulong, value: c_ulong) {
+    set_property(display, window, property, XA_CARDINAL, 32, &[value]);
+}
+
+pub unsafe fn set_window(display: *mut Display, window: c_ulong, property: c_ulong, value: c_ulong) {
+    set_property(display, window, property, XA_WINDOW, 32, &[value]);
+}
+
+pub unsafe fn set_windows(display: *mut Display, window: c_ulong, property: c_ulong, windows: &[c_ulong]) {
+    set_property(display, window, property, XA_WINDOW, 32, windows);
+}
+
+pub unsafe fn set_atoms(display: *mut Display, window: c_ulong, property: c_ulong, atoms: &[c_ulong]) {
+    set_property(display, window, property, XA_ATOM, 32, atoms);
+}
+
+pub unsafe fn set_utf8_string(display: *mut Display, window: c_ulong, utf8_string: c_ulong, property: c_ulong, value: &str) {
+    XChangeProperty(display, window, property, utf8_string, 8, PropModeReplace, value.as_ptr(), value.len() as c_int);
+}
+
+/// Reads a property made of 32 bit items, like a list of atoms or cardinals. Returns an empty list if
+/// the window doesn't have the property.
+pub unsafe fn get_longs(display: *mut Display, window: c_ulong, property: c_ulong) -> Vec<c_ulong> {
+    let mut actual_type = 0;
+    let mut actual_format = 0;
+    let mut count = 0;
+    let mut bytes_after = 0;
+    let mut data: *mut c_uchar = std::ptr::null_mut();
+    let result = XGetWindowProperty(display, window, property, 0, 1024, False, AnyPropertyType as c_ulong, &mut actual_type, &mut actual_format, &mut count, &mut bytes_after, &mut data);
+    if result != Success as c_int || data.is_null() {
+        return Vec::new();
+    }
+    let values = if actual_format == 32 {
+        std::slice::from_raw_parts(data as *const c_ulong, count as usize).to_vec()
+    } else {
+        Vec::new()
+    };
+    XFree(data as *mut _);
+    values
+}
+
+/// Reads a text property like `_NET_WM_NAME` of the given type, which can be `AnyPropertyType`.
+pub unsafe fn get_string(display: *mut Display, window: c_ulong, property: c_ulong, property_type: c_ulong) -> Option<String> {
+    let mut actual_type = 0;
+    let mut actual_format = 0;
+    let mut count = 0;
+    let mut bytes_after = 0;
+    let mut data: *mut c_uchar = std::ptr::null_mut();
+    let result = XGetWindowProperty(display, window, property, 0, 1024, False, property_type, &mut actual_type, &mut actual_format, &mut count, &mut bytes_after, &mut data);
+    if result != Success as c_int || data.is_null() {
+        return None;
+    }
+    let type_matches = property_type == AnyPropertyType as c_ulong || actual_type == property_type;
+    let value = (type_matches && actual_format == 8)
+        .then(|| String::from_utf8_lossy(std::slice::from_raw_parts(data, count as usize)).into_owned());
+    XFree(data as *mut _);
+    value
+}
+
+pub unsafe fn delete_property(display: *mut Display, window: c_ulong, property: c_ulong) {
+    XDeleteProperty(display, window, property);
+}
+
+/// Xlib expects the items of 32 bit properties as longs, whatever their size on the wire.
+unsafe fn set_property(display: *mut Display, window: c_ulong, property: c_ulong, ty: c_ulong, format: c_int, data: &[c_ulong]) {
+    XChangeProperty(display, window, property, ty, format, PropModeReplace, data.as_ptr() as *const c_uchar, data.len() as c_int);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strut() -> Strut {
+        Strut { left: 0, right: 0, top: 0, bottom: 0, left_start_y: 0, left_end_y: 0, right_start_y: 0, right_end_y: 0, top_start_x: 0, top_end_x: 0, bottom_start_x: 0, bottom_end_x: 0 }
+    }
+
+    #[test]
+    fn partial_strut_only_shrinks_the_outputs_it_covers() {
+        let left_output = Bounds::new(0, 0, 1920, 1080);
+        let right_output = Bounds::new(1920, 0, 1920, 1080);
+        let bar = Strut { top: 30, top_start_x: 0, top_end_x: 1919, ..strut() };
+        assert_eq!(bar.apply(&left_output, 3840, 1080), Bounds::new(0, 30, 1920, 1050));
+        assert_eq!(bar.apply(&right_output, 3840, 1080), right_output);
+    }
+
+    #[test]
+    fn struts_are_measured_from_the_screen_edges() {
+        let right_output = Bounds::new(1920, 0, 1920, 1080);
+        let dock = Strut { right: 50, right_start_y: 0, right_end_y: c_ulong::MAX, bottom: 20, bottom_start_x: 0, bottom_end_x: c_ulong::MAX, ..strut() };
+        assert_eq!(dock.apply(&right_output, 3840, 1080), Bounds::new(1920, 0, 1870, 1060));
+    }
+}
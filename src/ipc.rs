@@ -0,0 +1,346 @@
+use std::ffi::c_ulong;
+use std::io::{ErrorKind, Read, Write};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+use crate::util::{runtime_dir, Bounds, Direction};
+
+/// Returns the path of the IPC socket: `$XNEBULA_SOCKET` if set, otherwise `xnebula.sock` in
+/// `$XDG_RUNTIME_DIR`. Returns `None` if neither is set.
+pub fn socket_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("XNEBULA_SOCKET").filter(|path| !path.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+    Some(runtime_dir()?.join("xnebula.sock"))
+}
+
+pub type ConnectionId = u64;
+
+/// Connections that send a longer line are closed.
+const MAX_REQUEST_LENGTH: usize = 64 * 1024;
+/// Connections that don't read their replies and events fast enough to keep this much of them
+/// waiting are closed.
+const MAX_QUEUED_LENGTH: usize = 1024 * 1024;
+
+/// Something that happened in the window manager, sent to every connection that subscribed to it.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    WindowManaged {
+        window: c_ulong,
+        workspace: usize,
+    },
+    WindowUnmanaged {
+        window: c_ulong,
+    },
+    FocusChanged {
+        window: Option<c_ulong>,
+    },
+    LayoutChanged {
+        #[serde(serialize_with = "serialize_changed_windows")]
+        windows: Vec<(c_ulong, Bounds)>,
+    },
+    TilingDirectionChanged {
+        window: Option<c_ulong>,
+        direction: Direction,
+    },
+    ConfigReloaded,
+}
+
+impl Event {
+    pub const NAMES: [&'static str; 6] = ["window-managed", "window-unmanaged", "focus-changed", "layout-changed", "tiling-direction-changed", "config-reloaded"];
+}
+
+fn serialize_changed_windows<S: Serializer>(windows: &[(c_ulong, Bounds)], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(windows.iter().map(|(window, bounds)| serde_json::json!({ "window": window, "bounds": bounds })))
+}
+
+struct Connection {
+    id: ConnectionId,
+    stream: UnixStream,
+    buffer: Vec<u8>,
+    /// Replies and events that couldn't be written yet.
+    outgoing: Vec<u8>,
+    /// The events this connection subscribed to, `None` if it didn't subscribe.
+    subscription: Option<Vec<String>>,
+}
+
+/// A Unix domain socket that accepts newline-terminated commands and answers each of them with a
+/// single line of JSON.
+pub struct IpcServer {
+    listener: UnixListener,
+    path: PathBuf,
+    connections: Vec<Connection>,
+    next_id: ConnectionId,
+}
+
+impl IpcServer {
+    pub fn bind() -> std::io::Result<Self> {
+        let path = socket_path().ok_or_else(|| std::io::Error::new(ErrorKind::NotFound, "neither XNEBULA_SOCKET nor XDG_RUNTIME_DIR is set"))?;
+        Self::bind_at(path)
+    }
+
+    fn bind_at(path: PathBuf) -> std::io::Result<Self> {
+        if UnixStream::connect(&path).is_ok() {
+            return Err(std::io::Error::new(ErrorKind::AddrInUse, format!("{} is already in use", path.display())));
+        }
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            path,
+            connections: Vec::new(),
+            next_id: 0,
+        })
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Returns the file descriptors that have to be polled for incoming connections and requests, and for
+    /// connections that have output waiting.
+    pub fn poll_fds(&self) -> Vec<libc::pollfd> {
+        let poll_fd = |fd: RawFd, events| libc::pollfd {
+            fd,
+            events,
+            revents: 0,
+        };
+        let mut fds = vec![poll_fd(self.listener.as_raw_fd(), libc::POLLIN)];
+        fds.extend(self.connections.iter().map(|connection| {
+            let events = if connection.outgoing.is_empty() { libc::POLLIN } else { libc::POLLIN | libc::POLLOUT };
+            poll_fd(connection.stream.as_raw_fd(), events)
+        }));
+        fds
+    }
+
+    /// Accepts pending connections, writes waiting output and returns every complete request that arrived
+    /// since the last call.
+    pub fn receive(&mut self) -> Vec<(ConnectionId, String)> {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                self.connections.push(Connection {
+                    id: self.next_id,
+                    stream,
+                    buffer: Vec::new(),
+                    outgoing: Vec::new(),
+                    subscription: None,
+                });
+                self.next_id += 1;
+            }
+        }
+
+        let mut requests = Vec::new();
+        self.connections.retain_mut(|connection| {
+            if !connection.flush() {
+                return false;
+            }
+            let mut chunk = [0u8; 4096];
+            loop {
+                match connection.stream.read(&mut chunk) {
+                    Ok(0) => return false,
+                    Ok(read) => connection.buffer.extend_from_slice(&chunk[..read]),
+                    Err(error) if error.kind() == ErrorKind::WouldBlock => return true,
+                    Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                    Err(_) => return false,
+                }
+                while let Some(end) = connection.buffer.iter().position(|byte| *byte == b'\n') {
+                    let line: Vec<u8> = connection.buffer.drain(..=end).collect();
+                    let line = String::from_utf8_lossy(&line).trim().to_string();
+                    if !line.is_empty() {
+                        requests.push((connection.id, line));
+                    }
+                }
+                // Nothing a client sends comes close to this, it would only make the buffer grow forever
+                if connection.buffer.len() > MAX_REQUEST_LENGTH {
+                    return false;
+                }
+            }
+        });
+        requests
+    }
+
+    /// Sends a reply to a connection. Connections that can't be written to are closed.
+    pub fn send(&mut self, id: ConnectionId, reply: &Value) {
+        let Some(index) = self.connections.iter().position(|connection| connection.id == id) else {
+            return;
+        };
+        let mut line = reply.to_string();
+        line.push('\n');
+        if !self.connections[index].queue(line.as_bytes()) {
+            self.connections.remove(index);
+        }
+    }
+
+    /// Subscribes a connection to the given events, or to all of them if `events` is empty.
+    pub fn subscribe(&mut self, id: ConnectionId, events: Vec<String>) -> Result<(), String> {
+        if let Some(event) = events.iter().find(|event| !Event::NAMES.contains(&event.as_str())) {
+            return Err(format!("unknown event '{}', expected one of {}", event, Event::NAMES.join(", ")));
+        }
+        if let Some(connection) = self.connections.iter_mut().find(|connection| connection.id == id) {
+            connection.subscription = Some(events);
+        }
+        Ok(())
+    }
+
+    /// Sends an event to every connection that subscribed to it. Subscribers that fall too far behind
+    /// are closed instead of holding up the window manager.
+    pub fn broadcast(&mut self, event: &Event) {
+        let Ok(event) = serde_json::to_value(event) else {
+            return;
+        };
+        let name = event["event"].as_str().unwrap_or_default();
+        let mut line = event.to_string();
+        line.push('\n');
+        self.connections.retain_mut(|connection| match &connection.subscription {
+            Some(events) if events.is_empty() || events.iter().any(|event| event == name) => connection.queue(line.as_bytes()),
+            _ => true,
+        });
+    }
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl Connection {
+    /// Adds a line to the output and writes as much of it as possible without blocking. Returns false
+    /// if the connection has to be closed.
+    fn queue(&mut self, line: &[u8]) -> bool {
+        if self.outgoing.len() + line.len() > MAX_QUEUED_LENGTH {
+            return false;
+        }
+        self.outgoing.extend_from_slice(line);
+        self.flush()
+    }
+
+    /// Writes as much of the waiting output as possible without blocking. Returns false if the
+    /// connection has to be closed.
+    fn flush(&mut self) -> bool {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return false,
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+    use std::time::Duration;
+    use serde_json::json;
+    use super::*;
+
+    fn bind(name: &str) -> (IpcServer, PathBuf) {
+        let path = std::env::temp_dir().join(format!("xnebula-test-{}-{}.sock", name, std::process::id()));
+        (IpcServer::bind_at(path.clone()).unwrap(), path)
+    }
+
+    fn read_line(reader: &mut BufReader<&UnixStream>) -> String {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        line
+    }
+
+    #[test]
+    fn requests_are_split_into_lines_and_answered() {
+        let (mut server, path) = bind("requests");
+        let mut client = UnixStream::connect(&path).unwrap();
+
+        client.write_all(b"focus left\nworks").unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        let requests = server.receive();
+        assert_eq!(requests.iter().map(|(_, request)| request.as_str()).collect::<Vec<_>>(), ["focus left"]);
+        client.write_all(b"pace 2\n\n").unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        let (id, request) = server.receive().remove(0);
+        assert_eq!(request, "workspace 2");
+
+        server.send(id, &json!({ "success": true }));
+        assert_eq!(read_line(&mut BufReader::new(&client)), "{\"success\":true}\n");
+        drop(server);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn connections_sending_overlong_lines_are_closed() {
+        let (mut server, path) = bind("overlong");
+        let mut client = UnixStream::connect(&path).unwrap();
+        let mut other = UnixStream::connect(&path).unwrap();
+        server.receive();
+
+        client.write_all(&vec![b'a'; MAX_REQUEST_LENGTH + 1]).unwrap();
+        other.write_all(b"close\n").unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(server.receive(), [(1, "close".to_string())]);
+        assert_eq!(server.poll_fds().len(), 2);
+        assert_eq!(client.read(&mut [0; 1]).unwrap(), 0);
+    }
+
+    #[test]
+    fn events_only_reach_connections_that_subscribed_to_them() {
+        let (mut server, path) = bind("events");
+        let focus_only = UnixStream::connect(&path).unwrap();
+        let everything = UnixStream::connect(&path).unwrap();
+        let mut everything_reader = BufReader::new(&everything);
+        let unsubscribed = UnixStream::connect(&path).unwrap();
+        server.receive();
+        server.subscribe(0, vec!["focus-changed".to_string()]).unwrap();
+        server.subscribe(1, Vec::new()).unwrap();
+        assert!(server.subscribe(2, vec!["teleported".to_string()]).is_err());
+
+        server.broadcast(&Event::ConfigReloaded);
+        server.broadcast(&Event::FocusChanged { window: Some(5) });
+        assert_eq!(read_line(&mut BufReader::new(&focus_only)), "{\"event\":\"focus-changed\",\"window\":5}\n");
+        assert_eq!(read_line(&mut everything_reader), "{\"event\":\"config-reloaded\"}\n");
+        assert_eq!(read_line(&mut everything_reader), "{\"event\":\"focus-changed\",\"window\":5}\n");
+        unsubscribed.set_nonblocking(true).unwrap();
+        assert_eq!((&unsubscribed).read(&mut [0; 1]).unwrap_err().kind(), ErrorKind::WouldBlock);
+    }
+
+    #[test]
+    fn subscribers_that_stop_reading_are_dropped() {
+        let (mut server, path) = bind("stalled");
+        let stalled = UnixStream::connect(&path).unwrap();
+        server.receive();
+        server.subscribe(0, Vec::new()).unwrap();
+
+        let windows = (0..100).map(|window| (window, Bounds::new(0, 0, 100, 100))).collect::<Vec<_>>();
+        let mut sent = 0;
+        while server.poll_fds().len() == 2 {
+            server.broadcast(&Event::LayoutChanged { windows: windows.clone() });
+            sent += 1;
+            assert!(sent < 10_000, "the subscriber should have been dropped");
+        }
+        assert!(sent > 1);
+        let mut reader = BufReader::new(&stalled);
+        assert!(read_line(&mut reader).starts_with("{\"event\":\"layout-changed\""));
+    }
+
+    #[test]
+    fn event_names_match_the_sent_events() {
+        let events = [
+            Event::WindowManaged { window: 1, workspace: 1 },
+            Event::WindowUnmanaged { window: 1 },
+            Event::FocusChanged { window: None },
+            Event::LayoutChanged { windows: Vec::new() },
+            Event::TilingDirectionChanged { window: None, direction: Direction::Left },
+            Event::ConfigReloaded,
+        ];
+        let names: Vec<_> = events.iter().map(|event| serde_json::to_value(event).unwrap()["event"].clone()).collect();
+        assert_eq!(names, Event::NAMES);
+    }
+}
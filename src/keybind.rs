@@ -1,4 +1,7 @@
-use std::ffi::c_uint;
+use std::ffi::{c_uint, c_ulong, CString};
+use std::str::FromStr;
+use serde::{de, Deserialize, Deserializer};
+use x11::xlib::{ControlMask, Mod1Mask, Mod3Mask, Mod4Mask, Mod5Mask, NoSymbol, ShiftMask, XStringToKeysym};
 use crate::action::Action;
 
 pub struct Keybind {
@@ -16,3 +19,77 @@ impl Keybind {
         }
     }
 }
+
+/// A keysym together with the modifiers that have to be held, written like `Mod4+Shift+q`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeyCombination {
+    pub keysym: c_uint,
+    pub modifiers: c_uint,
+}
+
+impl KeyCombination {
+    pub fn new(keysym: c_uint, modifiers: c_uint) -> Self {
+        Self {
+            keysym,
+            modifiers,
+        }
+    }
+}
+
+impl FromStr for KeyCombination {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<&str> = s.split('+').map(str::trim).collect();
+        let key = parts.pop().filter(|key| !key.is_empty()).ok_or_else(|| format!("missing key in '{}'", s))?;
+        let mut modifiers = 0;
+        for modifier in parts {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "shift" => ShiftMask,
+                "control" | "ctrl" => ControlMask,
+                "mod1" | "alt" => Mod1Mask,
+                "mod3" => Mod3Mask,
+                "mod4" | "super" => Mod4Mask,
+                "mod5" => Mod5Mask,
+                // Keybinds work regardless of CapsLock and NumLock
+                "lock" | "mod2" => return Err(format!("modifier '{}' can't be bound, CapsLock and NumLock are ignored", modifier)),
+                _ => return Err(format!("unknown modifier '{}'", modifier)),
+            };
+        }
+        let name = CString::new(key).map_err(|_| format!("invalid key '{}'", key))?;
+        let keysym = unsafe { XStringToKeysym(name.as_ptr()) };
+        if keysym == NoSymbol as c_ulong {
+            return Err(format!("unknown key '{}'", key));
+        }
+        Ok(Self::new(keysym as c_uint, modifiers))
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyCombination {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let combination = String::deserialize(deserializer)?;
+        combination.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use x11::keysym::{XK_Return, XK_q};
+    use super::*;
+
+    #[test]
+    fn parses_key_combinations() {
+        assert_eq!("Mod4+Shift+q".parse(), Ok(KeyCombination::new(XK_q, Mod4Mask | ShiftMask)));
+        assert_eq!("super + ctrl + Return".parse(), Ok(KeyCombination::new(XK_Return, Mod4Mask | ControlMask)));
+        assert_eq!("q".parse(), Ok(KeyCombination::new(XK_q, 0)));
+    }
+
+    #[test]
+    fn rejects_invalid_key_combinations() {
+        assert_eq!("Mod4+".parse::<KeyCombination>(), Err("missing key in 'Mod4+'".to_string()));
+        assert_eq!("Hyper+q".parse::<KeyCombination>(), Err("unknown modifier 'Hyper'".to_string()));
+        assert_eq!("Mod4+nokey".parse::<KeyCombination>(), Err("unknown key 'nokey'".to_string()));
+        assert!("Lock+q".parse::<KeyCombination>().is_err());
+        assert!("Mod2+Mod4+q".parse::<KeyCombination>().is_err());
+    }
+}
@@ -0,0 +1,338 @@
+use std::ffi::c_ulong;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::layout::{ChangedWindows, Gaps, Layout, LayoutKind, SavedLayout, Window};
+use crate::util::{Bounds, Direction};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedMasterStack {
+    master_count: usize,
+    master_ratio: f32,
+    windows: Vec<SavedWindow>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SavedWindow {
+    window: c_ulong,
+    focused: bool,
+}
+
+/// A dwm-style layout: the first `master_count` windows share the master area on the left, all other
+/// windows are stacked on top of each other on the right.
+#[derive(Debug)]
+pub struct MasterStack {
+    windows: Vec<Window>,
+    focused: usize,
+    master_count: usize,
+    master_ratio: f32,
+    bounds: Bounds,
+    gaps: Gaps,
+}
+
+impl MasterStack {
+    pub fn new(bounds: Bounds, master_count: usize, master_ratio: f32) -> Self {
+        Self {
+            windows: Vec::new(),
+            focused: 0,
+            master_count,
+            master_ratio,
+            bounds,
+            gaps: Gaps::default(),
+        }
+    }
+
+    /// Rebuilds a saved layout, leaving out the windows for which `keep` returns false.
+    pub fn restore(saved: SavedMasterStack, bounds: Bounds, keep: &dyn Fn(c_ulong) -> bool) -> Self {
+        let windows: Vec<&SavedWindow> = saved.windows.iter().filter(|window| keep(window.window)).collect();
+        Self {
+            focused: windows.iter().position(|window| window.focused).unwrap_or(0),
+            windows: windows.iter().map(|window| Window::new(window.window)).collect(),
+            master_count: saved.master_count,
+            master_ratio: saved.master_ratio,
+            bounds,
+            gaps: Gaps::default(),
+        }
+    }
+
+    fn masters(&self) -> usize {
+        self.master_count.min(self.windows.len())
+    }
+
+    /// Returns the range of indices of the column the window at `index` is in.
+    fn column_of(&self, index: usize) -> (usize, usize) {
+        let masters = self.masters();
+        if index < masters {
+            (0, masters)
+        } else {
+            (masters, self.windows.len())
+        }
+    }
+
+    fn compute_bounds(&self) -> Vec<Bounds> {
+        let masters = self.masters();
+        let stacked = self.windows.len() - masters;
+        let (master_area, stack_area) = if masters == 0 {
+            (None, Some(self.bounds.clone()))
+        } else if stacked == 0 {
+            (Some(self.bounds.clone()), None)
+        } else {
+            let (master_area, stack_area) = self.bounds.split(Direction::Right, self.master_ratio);
+            (Some(master_area), Some(stack_area))
+        };
+
+        let mut bounds = Vec::new();
+        if let Some(master_area) = master_area {
+            bounds.extend(master_area.split_evenly(Direction::Down, masters));
+        }
+        if let Some(stack_area) = stack_area {
+            bounds.extend(stack_area.split_evenly(Direction::Down, stacked));
+        }
+        bounds
+    }
+
+    fn focused_id(&self) -> Option<c_ulong> {
+        self.windows.get(self.focused).map(|window| window.id)
+    }
+}
+
+impl Layout for MasterStack {
+    fn kind(&self) -> LayoutKind {
+        LayoutKind::MasterStack
+    }
+
+    fn insert(&mut self, new_window: Window) -> ChangedWindows {
+        self.windows.insert(0, new_window);
+        self.focused = 0;
+        self.arrange()
+    }
+
+    fn remove_window(&mut self, window_id: c_ulong) -> Option<ChangedWindows> {
+        let index = self.windows.iter().position(|window| window.id == window_id)?;
+        self.windows.remove(index);
+        if index < self.focused {
+            self.focused -= 1;
+        }
+        self.focused = self.focused.min(self.windows.len().saturating_sub(1));
+        Some(self.arrange())
+    }
+
+    fn move_focus(&mut self, direction: Direction) -> Option<c_ulong> {
+        if self.windows.is_empty() {
+            return None;
+        }
+        let masters = self.masters();
+        let (start, end) = self.column_of(self.focused);
+        let new_focused = match direction {
+            Direction::Up if self.focused > start => self.focused - 1,
+            Direction::Down if self.focused + 1 < end => self.focused + 1,
+            Direction::Left if self.focused >= masters && masters > 0 => (self.focused - masters).min(masters - 1),
+            Direction::Right if self.focused < masters && masters < self.windows.len() => masters + self.focused.min(self.windows.len() - masters - 1),
+            _ => return None,
+        };
+        self.focused = new_focused;
+        self.focused_id()
+    }
+
+    fn resize_focused_window(&mut self, direction: Direction, amount: f32) -> Option<ChangedWindows> {
+        let masters = self.masters();
+        if masters == 0 || masters == self.windows.len() {
+            return None;
+        }
+        let amount = match direction {
+            Direction::Right => amount,
+            Direction::Left => -amount,
+            Direction::Up | Direction::Down => return None,
+        };
+        let new_ratio = self.master_ratio + amount;
+        if !(0.01..=0.99).contains(&new_ratio) {
+            return None;
+        }
+        self.master_ratio = new_ratio;
+        Some(self.arrange())
+    }
+
+    fn change_master_count(&mut self, delta: i32) -> Option<ChangedWindows> {
+        let new_count = self.master_count.saturating_add_signed(delta as isize);
+        if new_count == self.master_count {
+            return None;
+        }
+        self.master_count = new_count;
+        Some(self.arrange())
+    }
+
+    fn set_bounds(&mut self, bounds: Bounds) {
+        self.bounds = bounds;
+    }
+
+    fn set_gaps(&mut self, gaps: Gaps) {
+        self.gaps = gaps;
+    }
+
+    fn arrange(&mut self) -> ChangedWindows {
+        let alone = self.windows.len() == 1;
+        self.windows.iter()
+            .zip(self.compute_bounds())
+            .map(|(window, bounds)| (window.id, self.gaps.apply(&bounds, &self.bounds, alone)))
+            .collect()
+    }
+
+    fn focus_window(&mut self, window_id: c_ulong) -> bool {
+        match self.windows.iter().position(|window| window.id == window_id) {
+            Some(index) => {
+                self.focused = index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn focused_window(&self) -> Option<c_ulong> {
+        self.focused_id()
+    }
+
+    fn windows(&self) -> Vec<c_ulong> {
+        self.windows.iter().rev().map(|window| window.id).collect()
+    }
+
+    fn save(&self) -> SavedLayout {
+        SavedLayout::MasterStack(SavedMasterStack {
+            master_count: self.master_count,
+            master_ratio: self.master_ratio,
+            windows: self.windows.iter()
+                .enumerate()
+                .map(|(index, window)| SavedWindow { window: window.id, focused: index == self.focused })
+                .collect(),
+        })
+    }
+
+    fn dump(&self) -> Value {
+        let masters = self.masters();
+        let windows: Vec<Value> = self.windows.iter()
+            .zip(self.compute_bounds())
+            .enumerate()
+            .map(|(index, (window, bounds))| json!({
+                "window": window.id,
+                "bounds": bounds,
+                "master": index < masters,
+                "focused": index == self.focused,
+            }))
+            .collect();
+        json!({
+            "layout": self.kind(),
+            "bounds": self.bounds,
+            "master_count": self.master_count,
+            "master_ratio": self.master_ratio,
+            "windows": windows,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::restore_layout;
+
+    /// The last window is the master, the others are stacked in the order they were inserted.
+    fn master_stack_with(windows: &[c_ulong]) -> MasterStack {
+        let mut layout = MasterStack::new(Bounds::new(0, 0, 1000, 600), 1, 0.5);
+        for window in windows {
+            layout.insert(Window::new(*window));
+        }
+        layout
+    }
+
+    #[test]
+    fn masters_are_on_the_left_and_the_others_stacked_on_the_right() {
+        let mut layout = master_stack_with(&[1, 2, 3]);
+        assert_eq!(layout.arrange(), vec![
+            (3, Bounds::new(0, 0, 500, 600)),
+            (2, Bounds::new(500, 0, 500, 300)),
+            (1, Bounds::new(500, 300, 500, 300)),
+        ]);
+        assert_eq!(layout.change_master_count(1), Some(vec![
+            (3, Bounds::new(0, 0, 500, 300)),
+            (2, Bounds::new(0, 300, 500, 300)),
+            (1, Bounds::new(500, 0, 500, 600)),
+        ]));
+        assert_eq!(layout.change_master_count(-5).map(|changed| changed.len()), Some(3));
+        assert_eq!(layout.change_master_count(-1), None);
+        assert_eq!(layout.arrange()[2], (1, Bounds::new(0, 400, 1000, 200)));
+    }
+
+    #[test]
+    fn focus_moves_between_and_within_the_columns() {
+        let mut layout = master_stack_with(&[1, 2, 3]);
+        assert_eq!(layout.focused_window(), Some(3));
+        assert_eq!(layout.move_focus(Direction::Left), None);
+        assert_eq!(layout.move_focus(Direction::Right), Some(2));
+        assert_eq!(layout.move_focus(Direction::Down), Some(1));
+        assert_eq!(layout.move_focus(Direction::Down), None);
+        assert_eq!(layout.move_focus(Direction::Left), Some(3));
+    }
+
+    #[test]
+    fn removing_a_window_keeps_the_focus_on_the_same_window() {
+        let mut layout = master_stack_with(&[1, 2, 3]);
+        layout.move_focus(Direction::Right);
+        layout.move_focus(Direction::Down);
+        assert_eq!(layout.remove_window(3).map(|changed| changed.len()), Some(2));
+        assert_eq!(layout.focused_window(), Some(1));
+        assert_eq!(layout.remove_window(3), None);
+        assert_eq!(layout.remove_window(1), Some(vec![(2, Bounds::new(0, 0, 1000, 600))]));
+        assert_eq!(layout.focused_window(), Some(2));
+    }
+
+    #[test]
+    fn dump_marks_masters_and_the_focused_window() {
+        let dump = master_stack_with(&[1, 2]).dump();
+        assert_eq!(dump["layout"], "master-stack");
+        assert_eq!(dump["windows"], json!([
+            { "window": 2, "bounds": Bounds::new(0, 0, 500, 600), "master": true, "focused": true },
+            { "window": 1, "bounds": Bounds::new(500, 0, 500, 600), "master": false, "focused": false },
+        ]));
+    }
+
+    #[test]
+    fn master_ratio_stays_within_bounds() {
+        let mut layout = master_stack_with(&[1, 2]);
+        assert_eq!(layout.resize_focused_window(Direction::Right, 0.1).unwrap()[0], (2, Bounds::new(0, 0, 600, 600)));
+        assert_eq!(layout.resize_focused_window(Direction::Up, 0.1), None);
+        assert_eq!(layout.resize_focused_window(Direction::Right, 0.5), None);
+    }
+
+    #[test]
+    fn master_count_saturates() {
+        let mut layout = master_stack_with(&[1, 2]);
+        assert!(layout.change_master_count(i32::MIN).is_some());
+        assert_eq!(layout.master_count, 0);
+        assert_eq!(layout.change_master_count(-1), None);
+        layout.change_master_count(i32::MAX);
+        assert_eq!(layout.master_count, i32::MAX as usize);
+    }
+
+    #[test]
+    fn saved_master_stack_restores_to_the_same_layout() {
+        let mut layout = master_stack_with(&[1, 2, 3]);
+        layout.change_master_count(1);
+        layout.resize_focused_window(Direction::Right, 0.1);
+        layout.focus_window(2);
+
+        let saved = serde_json::to_string(&layout.save()).unwrap();
+        let mut restored = restore_layout(serde_json::from_str(&saved).unwrap(), Bounds::new(0, 0, 1000, 600), &|_| true).unwrap();
+        assert_eq!(restored.arrange(), layout.arrange());
+        assert_eq!(restored.focused_window(), Some(2));
+        assert_eq!(restored.save(), layout.save());
+    }
+
+    #[test]
+    fn restoring_leaves_out_windows_that_are_gone() {
+        let mut layout = master_stack_with(&[1, 2, 3]);
+        layout.focus_window(2);
+        let SavedLayout::MasterStack(saved) = layout.save() else {
+            panic!("a master stack saves as a master stack");
+        };
+        let restored = MasterStack::restore(saved, Bounds::new(0, 0, 1000, 600), &|window| window != 3);
+        assert_eq!(restored.windows(), vec![1, 2]);
+        assert_eq!(restored.focused_window(), Some(2));
+    }
+}
@@ -0,0 +1,202 @@
+use std::ffi::c_ulong;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::config::Config;
+use crate::criteria::Criteria;
+use crate::util::{Bounds, Direction};
+
+mod master_stack;
+mod window_tree;
+
+pub use master_stack::{MasterStack, SavedMasterStack};
+pub use window_tree::{SavedTree, WindowTree};
+
+pub type ChangedWindows = Vec<(c_ulong, Bounds)>;
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Window {
+    pub id: c_ulong,
+}
+
+impl Window {
+    pub fn new(id: c_ulong) -> Self {
+        Self {
+            id,
+        }
+    }
+}
+
+/// The state of a layout that a restarted window manager continues with. It is kept apart from
+/// [`Layout::dump`], so that the IPC output can change without breaking restarts.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "layout", rename_all = "kebab-case")]
+pub enum SavedLayout {
+    Tree(SavedTree),
+    MasterStack(SavedMasterStack),
+}
+
+/// Space left free between tiled windows (`inner`) and between them and the edges of the layout
+/// (`outer`). With `smart`, a window that is alone in its layout gets no gaps.
+#[derive(Debug, PartialEq, Clone, Copy, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Gaps {
+    pub inner: i32,
+    pub outer: i32,
+    pub smart: bool,
+}
+
+impl Gaps {
+    /// Shrinks a tile of a layout that fills `area` to the bounds of its window. Tiles are computed
+    /// without gaps, so that proportions mean the same thing with and without them.
+    pub fn apply(&self, tile: &Bounds, area: &Bounds, alone: bool) -> Bounds {
+        if alone && self.smart {
+            return tile.clone();
+        }
+        let half = self.inner / 2;
+        let left = if tile.x <= area.x { self.outer } else { self.inner - half };
+        let top = if tile.y <= area.y { self.outer } else { self.inner - half };
+        let right = if tile.x + tile.width >= area.x + area.width { self.outer } else { half };
+        let bottom = if tile.y + tile.height >= area.y + area.height { self.outer } else { half };
+        let width = tile.width.saturating_sub(left).saturating_sub(right).max(1);
+        let height = tile.height.saturating_sub(top).saturating_sub(bottom).max(1);
+        Bounds::new(tile.x.saturating_add(left), tile.y.saturating_add(top), width, height)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GapKind {
+    Inner,
+    Outer,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LayoutKind {
+    Tree,
+    MasterStack,
+}
+
+impl FromStr for LayoutKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tree" => Ok(LayoutKind::Tree),
+            "master-stack" => Ok(LayoutKind::MasterStack),
+            _ => Err(format!("unknown layout '{}', expected tree or master-stack", s)),
+        }
+    }
+}
+
+pub trait Layout {
+    fn kind(&self) -> LayoutKind;
+
+    fn insert(&mut self, new_window: Window) -> ChangedWindows;
+
+    /// Removes a window and returns all windows whose bounds changed in the process.
+    fn remove_window(&mut self, window_id: c_ulong) -> Option<ChangedWindows>;
+
+    fn move_focus(&mut self, direction: Direction) -> Option<c_ulong>;
+
+    /// Focuses the given window. Returns false if it isn't part of the layout.
+    fn focus_window(&mut self, window_id: c_ulong) -> bool;
+
+    fn resize_focused_window(&mut self, direction: Direction, amount: f32) -> Option<ChangedWindows>;
+
+    fn change_tiling_direction(&mut self, _direction: Direction) {}
+
+    fn change_master_count(&mut self, _delta: i32) -> Option<ChangedWindows> {
+        None
+    }
+
+    fn set_bounds(&mut self, bounds: Bounds);
+
+    fn set_gaps(&mut self, gaps: Gaps);
+
+    /// Recomputes the bounds of every window in the layout.
+    fn arrange(&mut self) -> ChangedWindows;
+
+    fn focused_window(&self) -> Option<c_ulong>;
+
+    /// Returns all windows in the layout, in the order they should be re-inserted into another layout.
+    fn windows(&self) -> Vec<c_ulong>;
+
+    /// Describes the structure of the layout for IPC clients.
+    fn dump(&self) -> Value;
+
+    /// Saves the layout so that [`restore_layout`] can rebuild it exactly.
+    fn save(&self) -> SavedLayout;
+
+    /// Puts the window into the first placeholder whose criteria `matches` accepts. Returns `None` if
+    /// there is no such placeholder.
+    fn swallow(&mut self, _window: Window, _matches: &dyn Fn(&Criteria) -> bool) -> Option<ChangedWindows> {
+        None
+    }
+
+    /// Returns the contents of a layout file describing the layout, using `criteria` to describe each
+    /// window, windows it returns `None` for are left out. Returns `None` if the layout is empty or
+    /// can't be saved.
+    fn export(&self, _criteria: &dyn Fn(c_ulong) -> Option<Criteria>) -> Option<String> {
+        None
+    }
+}
+
+/// Rebuilds a layout from the output of [`Layout::save`]. Windows for which `keep` returns false are
+/// left out. Returns `None` if the saved layout is malformed.
+pub fn restore_layout(saved: SavedLayout, bounds: Bounds, keep: &dyn Fn(c_ulong) -> bool) -> Option<Box<dyn Layout>> {
+    Some(match saved {
+        SavedLayout::Tree(saved) => Box::new(WindowTree::restore(saved, bounds, keep)?),
+        SavedLayout::MasterStack(saved) => Box::new(MasterStack::restore(saved, bounds, keep)),
+    })
+}
+
+pub fn create_layout(kind: LayoutKind, bounds: Bounds, config: &Config) -> Box<dyn Layout> {
+    let mut layout: Box<dyn Layout> = match kind {
+        LayoutKind::Tree => Box::new(WindowTree::new(bounds)),
+        LayoutKind::MasterStack => Box::new(MasterStack::new(bounds, config.master_stack.master_count, config.master_stack.master_ratio)),
+    };
+    layout.set_gaps(config.gaps);
+    layout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gaps_separate_tiles_and_keep_them_off_the_edges() {
+        let area = Bounds::new(0, 0, 1000, 600);
+        let gaps = Gaps { inner: 9, outer: 10, smart: false };
+        let (left, right) = area.split(Direction::Right, 0.5);
+        let left = gaps.apply(&left, &area, false);
+        let right = gaps.apply(&right, &area, false);
+        assert_eq!(left, Bounds::new(10, 10, 486, 580));
+        assert_eq!(right, Bounds::new(505, 10, 485, 580));
+        assert_eq!(right.x - (left.x + left.width), 9);
+    }
+
+    #[test]
+    fn smart_gaps_only_leave_out_gaps_for_a_single_window() {
+        let area = Bounds::new(0, 0, 1000, 600);
+        let gaps = Gaps { inner: 8, outer: 10, smart: true };
+        assert_eq!(gaps.apply(&area, &area, true), area);
+        assert_eq!(gaps.apply(&area, &area, false), Bounds::new(10, 10, 980, 580));
+        assert_eq!(Gaps { smart: false, ..gaps }.apply(&area, &area, true), Bounds::new(10, 10, 980, 580));
+    }
+
+    #[test]
+    fn huge_gaps_leave_windows_at_least_a_pixel() {
+        let area = Bounds::new(0, 0, 1000, 600);
+        let gaps = Gaps { inner: i32::MAX, outer: i32::MAX, smart: false };
+        assert_eq!(gaps.apply(&area, &area, false), Bounds::new(i32::MAX, i32::MAX, 1, 1));
+    }
+
+    #[test]
+    fn saved_layouts_are_tagged_with_their_kind() {
+        let saved = MasterStack::new(Bounds::new(0, 0, 100, 100), 1, 0.5).save();
+        assert_eq!(serde_json::to_value(saved).unwrap()["layout"], "master-stack");
+        let saved = WindowTree::new(Bounds::new(0, 0, 100, 100)).save();
+        assert_eq!(serde_json::to_value(saved).unwrap()["layout"], "tree");
+    }
+}
@@ -0,0 +1,773 @@
+use std::ffi::c_ulong;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::criteria::Criteria;
+use crate::layout::{ChangedWindows, Gaps, Layout, LayoutKind, SavedLayout, Window};
+use crate::util::Bounds;
+use crate::util::Direction;
+
+type NodeIndex = usize;
+
+#[derive(Debug, PartialEq, Clone)]
+struct TreeNode {
+    index: NodeIndex,
+    parent: Option<NodeIndex>,
+    bounds: Bounds,
+    direction: Direction,
+    ty: TreeNodeTy,
+}
+
+impl TreeNode {
+    fn new(parent: Option<NodeIndex>, bounds: Bounds, direction: Direction, ty: TreeNodeTy) -> Self {
+        Self {
+            index: 0,
+            parent,
+            bounds,
+            direction,
+            ty,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum TreeNodeTy {
+    Leaf {
+        window: Window,
+    },
+    /// A leaf loaded from a layout file that waits for a window matching its criteria.
+    Placeholder {
+        criteria: Criteria,
+    },
+    Node {
+        left: NodeIndex,
+        right: NodeIndex,
+        focus: Direction,
+        proportions: f32,
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum SavedNode {
+    Split {
+        direction: Direction,
+        focus: Direction,
+        proportions: f32,
+        children: Vec<SavedNode>,
+    },
+    Window {
+        window: c_ulong,
+        direction: Direction,
+    },
+    Placeholder {
+        criteria: Criteria,
+        direction: Direction,
+    },
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedTree {
+    root: Option<SavedNode>,
+}
+
+/// A node of a layout file. Windows are described by criteria instead of ids, so the file can be
+/// loaded again after they were closed.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum LayoutFileNode {
+    Split {
+        direction: Direction,
+        #[serde(default = "default_proportions")]
+        proportions: f32,
+        children: Vec<LayoutFileNode>,
+    },
+    Window {
+        #[serde(rename = "match")]
+        criteria: Criteria,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        direction: Option<Direction>,
+    },
+}
+
+fn default_proportions() -> f32 {
+    0.5
+}
+
+#[derive(Debug)]
+pub struct WindowTree {
+    nodes: Vec<Option<TreeNode>>,
+    root: Option<NodeIndex>,
+    bounds: Bounds,
+    gaps: Gaps,
+}
+
+impl WindowTree {
+    pub fn new(bounds: Bounds) -> Self {
+        let nodes = vec![None; 20];
+        Self {
+            nodes,
+            root: None,
+            bounds,
+            gaps: Gaps::default(),
+        }
+    }
+
+    /// Rebuilds a saved tree. Windows for which `keep` returns false are left out and their siblings
+    /// take the place of their parents, just like when they are removed.
+    pub fn restore(saved: SavedTree, bounds: Bounds, keep: &dyn Fn(c_ulong) -> bool) -> Option<Self> {
+        let mut tree = Self::new(bounds);
+        if let Some(root) = saved.root {
+            tree.root = tree.add_saved_node(root, keep)?;
+        }
+        Some(tree)
+    }
+
+    /// Creates a tree of placeholders from a layout file written by [`Layout::export`].
+    pub fn load(file: &Value, bounds: Bounds) -> Result<Self, String> {
+        let root = LayoutFileNode::deserialize(file).map_err(|error| error.to_string())?;
+        let mut tree = Self::new(bounds);
+        tree.root = Some(tree.add_file_node(root)?);
+        Ok(tree)
+    }
+
+    fn add_file_node(&mut self, node: LayoutFileNode) -> Result<NodeIndex, String> {
+        let bounds = Bounds::new(0, 0, 0, 0);
+        match node {
+            LayoutFileNode::Window { criteria, direction } => {
+                Ok(self.add_node(TreeNode::new(None, bounds, direction.unwrap_or(Direction::Right), TreeNodeTy::Placeholder { criteria })))
+            }
+            LayoutFileNode::Split { direction, proportions, children } => {
+                if !(0.01..=0.99).contains(&proportions) {
+                    return Err(format!("proportions must be between 0.01 and 0.99, got {}", proportions));
+                }
+                let [left, right]: [LayoutFileNode; 2] = children.try_into()
+                    .map_err(|children: Vec<_>| format!("a split needs exactly 2 children, got {}", children.len()))?;
+                let left = self.add_file_node(left)?;
+                let right = self.add_file_node(right)?;
+                let focus = direction.invert();
+                let index = self.add_node(TreeNode::new(None, bounds, direction, TreeNodeTy::Node { left, right, focus, proportions }));
+                self.get_node_mut(left).parent = Some(index);
+                self.get_node_mut(right).parent = Some(index);
+                Ok(index)
+            }
+        }
+    }
+
+    /// Windows without criteria are left out, their sibling takes the place of the split.
+    fn export_node(&self, index: NodeIndex, criteria: &dyn Fn(c_ulong) -> Option<Criteria>) -> Option<LayoutFileNode> {
+        let node = self.get_node(index);
+        match &node.ty {
+            TreeNodeTy::Node { left, right, proportions, .. } => {
+                match (self.export_node(*left, criteria), self.export_node(*right, criteria)) {
+                    (Some(left), Some(right)) => Some(LayoutFileNode::Split {
+                        direction: node.direction.clone(),
+                        proportions: *proportions,
+                        children: vec![left, right],
+                    }),
+                    (left, right) => left.or(right),
+                }
+            }
+            TreeNodeTy::Leaf { window } => Some(LayoutFileNode::Window {
+                criteria: criteria(window.id)?,
+                direction: Some(node.direction.clone()),
+            }),
+            TreeNodeTy::Placeholder { criteria } => Some(LayoutFileNode::Window {
+                criteria: criteria.clone(),
+                direction: Some(node.direction.clone()),
+            }),
+        }
+    }
+
+    fn save_node(&self, index: NodeIndex) -> SavedNode {
+        let node = self.get_node(index);
+        let direction = node.direction.clone();
+        match &node.ty {
+            TreeNodeTy::Node { left, right, focus, proportions } => SavedNode::Split {
+                direction,
+                focus: focus.clone(),
+                proportions: *proportions,
+                children: vec![self.save_node(*left), self.save_node(*right)],
+            },
+            TreeNodeTy::Leaf { window } => SavedNode::Window { window: window.id, direction },
+            TreeNodeTy::Placeholder { criteria } => SavedNode::Placeholder { criteria: criteria.clone(), direction },
+        }
+    }
+
+    /// Adds a saved subtree without a parent. Returns `Some(None)` if none of its windows are kept and
+    /// `None` if the saved tree is malformed.
+    fn add_saved_node(&mut self, node: SavedNode, keep: &dyn Fn(c_ulong) -> bool) -> Option<Option<NodeIndex>> {
+        let bounds = Bounds::new(0, 0, 0, 0);
+        match node {
+            SavedNode::Window { window, direction } => {
+                if !keep(window) {
+                    return Some(None);
+                }
+                Some(Some(self.add_node(TreeNode::new(None, bounds, direction, TreeNodeTy::Leaf { window: Window::new(window) }))))
+            }
+            SavedNode::Placeholder { criteria, direction } => {
+                Some(Some(self.add_node(TreeNode::new(None, bounds, direction, TreeNodeTy::Placeholder { criteria }))))
+            }
+            SavedNode::Split { direction, focus, proportions, children } => {
+                let [left, right]: [SavedNode; 2] = children.try_into().ok()?;
+                let left = self.add_saved_node(left, keep)?;
+                let right = self.add_saved_node(right, keep)?;
+                match (left, right) {
+                    (Some(left), Some(right)) => {
+                        let index = self.add_node(TreeNode::new(None, bounds, direction, TreeNodeTy::Node { left, right, focus, proportions }));
+                        self.get_node_mut(left).parent = Some(index);
+                        self.get_node_mut(right).parent = Some(index);
+                        Some(Some(index))
+                    }
+                    (child, None) | (None, child) => Some(child),
+                }
+            }
+        }
+    }
+
+    fn apply_bounds(&mut self, index: NodeIndex) -> ChangedWindows {
+        let mut changed = Vec::new();
+        let mut nodes = Vec::new();
+        nodes.push(index);
+        let mut i = 0;
+        while i < nodes.len() {
+            let node = self.get_node_mut(nodes[i]);
+            let parent_index = node.parent;
+            let index = node.index;
+            let bounds = if let Some(parent_index) = parent_index {
+                let parent = self.get_node(parent_index);
+                if let TreeNodeTy::Node { left, proportions, .. } = parent.ty {
+                    let direction = parent.direction.clone();
+                    let (bounds_left, bounds_right) = parent.bounds.split(direction, proportions);
+                    if index == left {
+                        bounds_left
+                    } else {
+                        bounds_right
+                    }
+                } else {
+                    Bounds::new(0, 0, 0, 0)
+                }
+            } else {
+                self.bounds.clone()
+            };
+            let node = self.get_node_mut(nodes[i]);
+            node.bounds = bounds.clone();
+            match &node.ty {
+                TreeNodeTy::Node { left, right, .. } => {
+                    nodes.push(*left);
+                    nodes.push(*right);
+                }
+                TreeNodeTy::Leaf { window } => {
+                    changed.push((window.id, bounds.clone()));
+                }
+                TreeNodeTy::Placeholder { .. } => {}
+            }
+            i += 1;
+        }
+        self.with_gaps(changed)
+    }
+
+    /// Turns the bounds of leaves into the bounds of their windows.
+    fn with_gaps(&self, changed: ChangedWindows) -> ChangedWindows {
+        let alone = self.root.is_some_and(|root_index| matches!(self.get_node(root_index).ty, TreeNodeTy::Leaf { .. }));
+        changed.into_iter()
+            .map(|(window_id, bounds)| (window_id, self.gaps.apply(&bounds, &self.bounds, alone)))
+            .collect()
+    }
+
+    /// Removes a leaf and lets its sibling take the place of their parent.
+    fn remove_leaf(&mut self, index: NodeIndex) -> ChangedWindows {
+        let Some(parent_index) = self.get_node(index).parent else {
+            self.nodes[index] = None;
+            self.root = None;
+            return Vec::new();
+        };
+        let parent = self.get_node(parent_index);
+        let grandparent_index = parent.parent;
+        let other_index = match parent.ty {
+            TreeNodeTy::Node { left, right, .. } => if left == index { right } else { left },
+            TreeNodeTy::Leaf { .. } | TreeNodeTy::Placeholder { .. } => unreachable!("the parent of a node is always an inner node"),
+        };
+        if let Some(grandparent_index) = grandparent_index {
+            let grandparent = self.get_node_mut(grandparent_index);
+            if let TreeNodeTy::Node { ref mut left, ref mut right, .. } = grandparent.ty {
+                if *left == parent_index {
+                    *left = other_index;
+                } else {
+                    *right = other_index;
+                }
+            }
+        } else {
+            self.root = Some(other_index);
+        }
+        self.get_node_mut(other_index).parent = grandparent_index;
+        self.nodes[index] = None;
+        self.nodes[parent_index] = None;
+        self.apply_bounds(other_index)
+    }
+
+    fn find_window(&self, window_id: c_ulong) -> Option<NodeIndex> {
+        self.nodes.iter()
+            .flatten()
+            .find(|node| matches!(node.ty, TreeNodeTy::Leaf { window } if window.id == window_id))
+            .map(|node| node.index)
+    }
+
+    fn dump_node(&self, index: NodeIndex, focused_index: Option<NodeIndex>) -> Value {
+        let node = self.get_node(index);
+        match &node.ty {
+            TreeNodeTy::Node { left, right, focus, proportions } => json!({
+                "type": "split",
+                "direction": node.direction,
+                "focus": focus,
+                "proportions": proportions,
+                "bounds": node.bounds,
+                "children": [self.dump_node(*left, focused_index), self.dump_node(*right, focused_index)],
+            }),
+            TreeNodeTy::Leaf { window } => json!({
+                "type": "window",
+                "window": window.id,
+                "direction": node.direction,
+                "bounds": node.bounds,
+                "focused": Some(index) == focused_index,
+            }),
+            TreeNodeTy::Placeholder { criteria } => json!({
+                "type": "placeholder",
+                "criteria": criteria,
+                "direction": node.direction,
+                "bounds": node.bounds,
+                "focused": Some(index) == focused_index,
+            }),
+        }
+    }
+
+    /// Follows the focus down from the root. Placeholders are only focused if the tree has no windows,
+    /// otherwise the focus goes to the other side of the split.
+    fn get_focused_index(&self) -> Option<NodeIndex> {
+        let mut index = self.root?;
+        while let TreeNodeTy::Node { left, right, focus, .. } = &self.get_node(index).ty {
+            let (focused, other) = if *focus == self.get_node(index).direction { (*right, *left) } else { (*left, *right) };
+            index = if self.has_windows(focused) || !self.has_windows(other) { focused } else { other };
+        }
+        Some(index)
+    }
+
+    fn get_focused_node(&self) -> Option<&TreeNode> {
+        self.get_focused_index().map(|index| self.get_node(index))
+    }
+
+    fn has_windows(&self, index: NodeIndex) -> bool {
+        match &self.get_node(index).ty {
+            TreeNodeTy::Leaf { .. } => true,
+            TreeNodeTy::Placeholder { .. } => false,
+            TreeNodeTy::Node { left, right, .. } => self.has_windows(*left) || self.has_windows(*right),
+        }
+    }
+
+    fn get_node(&self, index: NodeIndex) -> &TreeNode {
+        let node = self.nodes[index].as_ref().unwrap();
+        node
+    }
+
+    fn get_node_mut(&mut self, index: NodeIndex) -> &mut TreeNode {
+        let node = self.nodes[index].as_mut().unwrap();
+        node
+    }
+
+    fn add_node(&mut self, mut node: TreeNode) -> NodeIndex {
+        let index = self.get_empty_index();
+        node.index = index;
+        self.nodes[index] = Some(node);
+        index
+    }
+
+    fn get_empty_index(&mut self) -> NodeIndex {
+        for i in 0..self.nodes.len() {
+            if self.nodes[i].is_none() {
+                return i as NodeIndex;
+            }
+        }
+        self.nodes.push(None);
+        (self.nodes.len() -  1) as NodeIndex
+    }
+}
+
+impl Layout for WindowTree {
+    fn kind(&self) -> LayoutKind {
+        LayoutKind::Tree
+    }
+
+    fn insert(&mut self, new_window: Window) -> ChangedWindows {
+        let mut changed = Vec::new();
+        if self.root.is_some() {
+            let focused_index = self.get_focused_index().unwrap();
+            let focused_node = self.get_node(focused_index);
+            let current_direction = focused_node.direction.clone();
+            let parent_index = focused_node.parent;
+            // Placeholders are split like windows, the new window doesn't get swallowed by them
+            if let TreeNodeTy::Leaf { .. } | TreeNodeTy::Placeholder { .. } = focused_node.ty {
+                let focused_ty = focused_node.ty.clone();
+                let proportions = 0.5f32;
+                let bounds = focused_node.bounds.clone();
+                let (bounds_left, bounds_right) = bounds.split(current_direction.clone(), proportions);
+                if let TreeNodeTy::Leaf { window } = focused_ty {
+                    changed.push((window.id, bounds_left.clone()));
+                }
+                changed.push((new_window.id, bounds_right.clone()));
+                let next_direction = match current_direction.clone() {
+                    Direction::Right | Direction::Left => Direction::Down,
+                    Direction::Down | Direction::Up => Direction::Right,
+                };
+                let left = self.add_node(TreeNode::new(Some(focused_index), bounds_left, current_direction.clone(), focused_ty));
+                let right = self.add_node(TreeNode::new(Some(focused_index), bounds_right, next_direction.clone(), TreeNodeTy::Leaf { window: new_window }));
+                self.nodes[focused_index] = Some(TreeNode {
+                    index: focused_index,
+                    parent: parent_index,
+                    bounds,
+                    direction: current_direction.clone(),
+                    ty: TreeNodeTy::Node {
+                        left,
+                        right,
+                        focus: current_direction.clone(),
+                        proportions,
+                    },
+                });
+            }
+        } else {
+            let root_index = self.add_node(TreeNode::new(
+                None,
+                self.bounds.clone(),
+                Direction::Right,
+                TreeNodeTy::Leaf {
+                    window: new_window,
+                },
+            ));
+            self.root = Some(root_index);
+            changed.push((new_window.id, self.bounds.clone()));
+        }
+        self.with_gaps(changed)
+    }
+
+    fn move_focus(&mut self, direction: Direction) -> Option<c_ulong> {
+        let mut index = self.get_focused_index()?;
+        // Find the closest split with a window on the other side of the focused one in that direction
+        while let Some(parent_index) = self.get_node(index).parent {
+            let parent = self.get_node(parent_index);
+            if let TreeNodeTy::Node { left, right, .. } = parent.ty {
+                let target = if direction == parent.direction { right } else { left };
+                if parent.direction.is_along_same_axis(direction.clone()) && target != index && self.has_windows(target) {
+                    if let TreeNodeTy::Node { ref mut focus, .. } = self.get_node_mut(parent_index).ty {
+                        *focus = direction;
+                    }
+                    return self.focused_window();
+                }
+            }
+            index = parent_index;
+        }
+        None
+    }
+
+    fn focus_window(&mut self, window_id: c_ulong) -> bool {
+        let Some(mut index) = self.find_window(window_id) else {
+            return false;
+        };
+        // Point the focus of every ancestor towards the window
+        while let Some(parent_index) = self.get_node(index).parent {
+            let parent = self.get_node_mut(parent_index);
+            let direction = parent.direction.clone();
+            if let TreeNodeTy::Node { right, ref mut focus, .. } = parent.ty {
+                *focus = if right == index { direction } else { direction.invert() };
+            }
+            index = parent_index;
+        }
+        true
+    }
+
+    fn remove_window(&mut self, window_id: c_ulong) -> Option<ChangedWindows> {
+        let index = self.find_window(window_id)?;
+        Some(self.remove_leaf(index))
+    }
+
+    fn change_tiling_direction(&mut self, direction: Direction) {
+        if let Some(focused_index) = self.get_focused_index() {
+            let focused_node = self.get_node_mut(focused_index);
+            focused_node.direction = direction;
+        }
+    }
+
+    fn resize_focused_window(&mut self, direction: Direction, amount: f32) -> Option<ChangedWindows> {
+        if self.root.is_some() {
+            let focused_index = self.get_focused_index().unwrap();
+            let focused_node = self.get_node(focused_index);
+            if let Some(parent_index) = focused_node.parent {
+                let mut node = self.get_node(parent_index);
+                loop {
+                    if node.direction.is_along_same_axis(direction.clone()) {
+                        break;
+                    }
+                    if let Some(parent) = node.parent {
+                        node = self.get_node(parent);
+                    } else {
+                        return None;
+                    }
+                }
+                let node = self.get_node_mut(node.index);
+                let node_direction = node.direction.clone();
+                if let TreeNodeTy::Node { ref mut proportions, .. } = node.ty {
+                    let amount = if node_direction == direction {
+                        amount
+                    } else {
+                        -amount
+                    };
+                    let new_proportions = *proportions + amount;
+                    if (0.01..=0.99).contains(&new_proportions) {
+                        *proportions = new_proportions;
+                    } else {
+                        return None;
+                    }
+                }
+                let node_index = node.index;
+                return Some(self.apply_bounds(node_index));
+            }
+        }
+        None
+    }
+
+    fn set_bounds(&mut self, bounds: Bounds) {
+        self.bounds = bounds;
+    }
+
+    fn set_gaps(&mut self, gaps: Gaps) {
+        self.gaps = gaps;
+    }
+
+    fn arrange(&mut self) -> ChangedWindows {
+        match self.root {
+            Some(root_index) => self.apply_bounds(root_index),
+            None => Vec::new(),
+        }
+    }
+
+    fn focused_window(&self) -> Option<c_ulong> {
+        if let Some(TreeNode { ty: TreeNodeTy::Leaf { window }, .. }) = self.get_focused_node() {
+            return Some(window.id);
+        }
+        None
+    }
+
+    fn windows(&self) -> Vec<c_ulong> {
+        let mut windows = Vec::new();
+        let mut stack = Vec::new();
+        if let Some(root_index) = self.root {
+            stack.push(root_index);
+        }
+        while let Some(index) = stack.pop() {
+            match &self.get_node(index).ty {
+                TreeNodeTy::Node { left, right, .. } => {
+                    stack.push(*right);
+                    stack.push(*left);
+                }
+                TreeNodeTy::Leaf { window } => {
+                    windows.push(window.id);
+                }
+                TreeNodeTy::Placeholder { .. } => {}
+            }
+        }
+        windows
+    }
+
+    fn swallow(&mut self, window: Window, matches: &dyn Fn(&Criteria) -> bool) -> Option<ChangedWindows> {
+        // Go through the tree in order, so that equal placeholders are filled from left to right
+        let mut stack = Vec::from_iter(self.root);
+        let index = loop {
+            let index = stack.pop()?;
+            match &self.get_node(index).ty {
+                TreeNodeTy::Node { left, right, .. } => {
+                    stack.push(*right);
+                    stack.push(*left);
+                }
+                TreeNodeTy::Placeholder { criteria } if matches(criteria) => break index,
+                _ => {}
+            }
+        };
+        let node = self.get_node_mut(index);
+        node.ty = TreeNodeTy::Leaf { window };
+        let bounds = node.bounds.clone();
+        self.focus_window(window.id);
+        Some(self.with_gaps(vec![(window.id, bounds)]))
+    }
+
+    fn export(&self, criteria: &dyn Fn(c_ulong) -> Option<Criteria>) -> Option<String> {
+        let root = self.export_node(self.root?, criteria)?;
+        serde_json::to_string_pretty(&root).ok()
+    }
+
+    fn save(&self) -> SavedLayout {
+        SavedLayout::Tree(SavedTree {
+            root: self.root.map(|root_index| self.save_node(root_index)),
+        })
+    }
+
+    fn dump(&self) -> Value {
+        let focused_index = self.get_focused_index();
+        json!({
+            "layout": self.kind(),
+            "bounds": self.bounds,
+            "root": self.root.map(|root_index| self.dump_node(root_index, focused_index)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+    use crate::criteria::WindowProperties;
+    use crate::layout::restore_layout;
+
+    fn bounds() -> Bounds {
+        Bounds::new(0, 0, 1000, 600)
+    }
+
+    /// Builds `1 | (2 / 3 / ...)`, the first window on the left and the others stacked on the right.
+    fn tree_with(windows: &[c_ulong]) -> WindowTree {
+        let mut tree = WindowTree::new(bounds());
+        for window in windows {
+            tree.insert(Window::new(*window));
+        }
+        tree
+    }
+
+    #[test]
+    fn removing_a_window_lets_its_sibling_take_its_place() {
+        let mut tree = tree_with(&[1, 2, 3]);
+        assert_eq!(tree.remove_window(2), Some(vec![(3, Bounds::new(500, 0, 500, 600))]));
+        assert_eq!(tree.windows(), vec![1, 3]);
+        assert_eq!(tree.remove_window(2), None);
+        assert_eq!(tree.remove_window(1), Some(vec![(3, bounds())]));
+        assert_eq!(tree.remove_window(3), Some(Vec::new()));
+        assert_eq!(tree.focused_window(), None);
+    }
+
+    #[test]
+    fn saved_tree_restores_to_the_same_tree() {
+        let mut tree = tree_with(&[1, 2, 3]);
+        tree.resize_focused_window(Direction::Left, 0.1);
+        tree.focus_window(1);
+
+        let saved = serde_json::to_string(&tree.save()).unwrap();
+        let mut restored = restore_layout(serde_json::from_str(&saved).unwrap(), bounds(), &|_| true).unwrap();
+        assert_eq!(restored.arrange(), tree.arrange());
+        assert_eq!(restored.focused_window(), Some(1));
+        assert_eq!(restored.save(), tree.save());
+    }
+
+    #[test]
+    fn restoring_leaves_out_windows_that_are_gone() {
+        let SavedLayout::Tree(saved) = tree_with(&[1, 2, 3]).save() else {
+            panic!("a tree saves as a tree");
+        };
+        let mut restored = WindowTree::restore(saved, bounds(), &|window| window != 2).unwrap();
+        assert_eq!(restored.windows(), vec![1, 3]);
+        assert_eq!(restored.arrange(), vec![(1, Bounds::new(0, 0, 500, 600)), (3, Bounds::new(500, 0, 500, 600))]);
+    }
+
+    #[test]
+    fn loaded_layout_puts_matching_windows_into_placeholders() {
+        let file = json!({
+            "type": "split",
+            "direction": "right",
+            "proportions": 0.6,
+            "children": [
+                { "type": "window", "match": { "class": "Emacs" } },
+                {
+                    "type": "split",
+                    "direction": "down",
+                    "children": [
+                        { "type": "window", "match": { "class": "Alacritty" } },
+                        { "type": "window", "match": { "class": "firefox" } },
+                    ],
+                },
+            ],
+        });
+        let mut tree = WindowTree::load(&file, bounds()).unwrap();
+        assert!(tree.arrange().is_empty());
+
+        let firefox = WindowProperties { class: "firefox".to_string(), ..Default::default() };
+        let changed = tree.swallow(Window::new(7), &|criteria| criteria.matches(&firefox));
+        assert_eq!(changed, Some(vec![(7, Bounds::new(600, 300, 400, 300))]));
+        assert_eq!(tree.focused_window(), Some(7));
+        assert_eq!(tree.swallow(Window::new(8), &|criteria| criteria.matches(&firefox)), None);
+
+        // Placeholders that are still empty survive a restart
+        let restored = restore_layout(tree.save(), bounds(), &|_| true).unwrap();
+        assert_eq!(restored.save(), tree.save());
+    }
+
+    #[test]
+    fn exported_layout_loads_again() {
+        let tree = tree_with(&[1, 2]);
+        let criteria = |window: c_ulong| Some(Criteria { class: Some(format!("app{}", window)), ..Default::default() });
+        let file: Value = serde_json::from_str(&tree.export(&criteria).unwrap()).unwrap();
+        let loaded = WindowTree::load(&file, bounds()).unwrap();
+        assert_eq!(loaded.export(&criteria), tree.export(&criteria));
+    }
+
+    #[test]
+    fn export_leaves_out_windows_without_criteria() {
+        let tree = tree_with(&[1, 2, 3]);
+        let criteria = |window: c_ulong| Some(Criteria { class: Some(format!("app{}", window)), ..Default::default() });
+        let without_2 = |window: c_ulong| criteria(window).filter(|_| window != 2);
+        let file: Value = serde_json::from_str(&tree.export(&without_2).unwrap()).unwrap();
+        let classes: Vec<_> = file["children"].as_array().unwrap().iter().map(|child| &child["match"]["class"]).collect();
+        assert_eq!(classes, [&json!("app1"), &json!("app3")]);
+        assert_eq!(tree.export(&|_| None), None);
+    }
+
+    #[test]
+    fn focus_skips_placeholders() {
+        let window = |class: &str| json!({ "type": "window", "match": { "class": class } });
+        let file = json!({
+            "type": "split",
+            "direction": "right",
+            "children": [
+                window("a"),
+                { "type": "split", "direction": "right", "children": [window("b"), window("c")] },
+            ],
+        });
+        let mut tree = WindowTree::load(&file, bounds()).unwrap();
+        assert_eq!(tree.focused_window(), None);
+        assert_eq!(tree.move_focus(Direction::Right), None);
+
+        let swallow = |tree: &mut WindowTree, window: c_ulong, class: &str| {
+            let properties = WindowProperties { class: class.to_string(), ..Default::default() };
+            tree.swallow(Window::new(window), &|criteria| criteria.matches(&properties))
+        };
+        swallow(&mut tree, 1, "a");
+        swallow(&mut tree, 3, "c");
+        tree.focus_window(1);
+        assert_eq!(tree.move_focus(Direction::Right), Some(3));
+        assert_eq!(tree.move_focus(Direction::Left), Some(1));
+        assert_eq!(tree.move_focus(Direction::Left), None);
+        assert_eq!(tree.focused_window(), Some(1));
+
+        // The placeholder between them isn't focused even if the focus of its split points there
+        tree.focus_window(3);
+        tree.remove_window(3);
+        assert_eq!(tree.focused_window(), Some(1));
+        assert_eq!(tree.move_focus(Direction::Right), None);
+    }
+
+    #[test]
+    fn invalid_layout_files_are_rejected() {
+        let window = json!({ "type": "window", "match": { "class": "a" } });
+        let files = [
+            json!({ "type": "split", "direction": "right", "children": [window.clone()] }),
+            json!({ "type": "split", "direction": "right", "proportions": 1.5, "children": [window.clone(), window.clone()] }),
+            json!({ "type": "window", "match": { "name": "a" } }),
+            json!({ "type": "tab", "children": [] }),
+        ];
+        for file in files {
+            assert!(WindowTree::load(&file, bounds()).is_err(), "{}", file);
+        }
+    }
+}
@@ -1,6 +1,19 @@
+#![allow(clippy::missing_safety_doc)]
+
+pub mod ipc;
 pub mod window_manager;
 mod action;
+mod atoms;
+mod client;
+mod colors;
 mod keybind;
 mod config;
+mod criteria;
+mod ewmh;
 mod layout;
+mod output;
+mod restart;
+mod rules;
+mod signals;
 mod util;
+mod workspace;
@@ -1,8 +1,43 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
 use xnebula::window_manager::WindowManager;
 
-fn main() {
+const USAGE: &str = "Usage: xnebula [--replace]";
+
+fn main() -> ExitCode {
+    let mut replace = false;
+    let mut restore = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--replace" => replace = true,
+            // Passed to the new process by a restart
+            "--restore" => match args.next() {
+                Some(path) => restore = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("--restore needs a path");
+                    return ExitCode::from(2);
+                }
+            },
+            _ => {
+                eprintln!("Unknown argument '{}'", arg);
+                eprintln!("{}", USAGE);
+                return ExitCode::from(2);
+            }
+        }
+    }
+
     unsafe {
-        let mut window_manager = WindowManager::new();
-        window_manager.run();
+        let mut window_manager = match WindowManager::new(replace) {
+            Ok(window_manager) => window_manager,
+            Err(error) => {
+                eprintln!("{}", error);
+                return ExitCode::FAILURE;
+            }
+        };
+        if let Some(path) = restore {
+            window_manager.restore(&path);
+        }
+        window_manager.run()
     }
 }
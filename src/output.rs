@@ -0,0 +1,164 @@
+use std::ffi::{c_int, c_ulong, CStr};
+use x11::xlib::{Display, XDisplayHeight, XDisplayWidth, XDefaultScreen};
+use x11_dl::xrandr::{RR_Connected, RRScreenChangeNotifyMask, Xrandr};
+use crate::config::Monitor;
+use crate::util::{Bounds, Direction};
+
+#[derive(Debug)]
+pub struct Output {
+    pub name: String,
+    pub bounds: Bounds,
+    pub workspace: usize,
+}
+
+impl Output {
+    pub fn new(name: String, bounds: Bounds, workspace: usize) -> Self {
+        Self {
+            name,
+            bounds,
+            workspace,
+        }
+    }
+}
+
+/// Finds the output that lies closest to `outputs[from]` in the given direction and overlaps with it on
+/// the other axis.
+pub fn find_neighbour(outputs: &[Output], from: usize, direction: Direction) -> Option<usize> {
+    let current = &outputs[from].bounds;
+    outputs.iter()
+        .enumerate()
+        .filter(|(index, _)| *index != from)
+        .filter_map(|(index, output)| {
+            let other = &output.bounds;
+            let overlaps_vertically = other.y < current.y + current.height && current.y < other.y + other.height;
+            let overlaps_horizontally = other.x < current.x + current.width && current.x < other.x + other.width;
+            let distance = match direction {
+                Direction::Right if overlaps_vertically => other.x - (current.x + current.width),
+                Direction::Left if overlaps_vertically => current.x - (other.x + other.width),
+                Direction::Down if overlaps_horizontally => other.y - (current.y + current.height),
+                Direction::Up if overlaps_horizontally => current.y - (other.y + other.height),
+                _ => return None,
+            };
+            if distance >= 0 { Some((index, distance)) } else { None }
+        })
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(index, _)| index)
+}
+
+pub struct RandR {
+    xrandr: Xrandr,
+    pub event_base: c_int,
+}
+
+impl RandR {
+    /// Loads libXrandr and selects screen change events on the root window. Returns `None` if either the
+    /// library or the extension is unavailable.
+    pub unsafe fn init(display: *mut Display, root_window: c_ulong) -> Option<Self> {
+        let xrandr = match Xrandr::open() {
+            Ok(xrandr) => xrandr,
+            Err(error) => {
+                eprintln!("Couldn't load libXrandr: {}", error);
+                return None;
+            }
+        };
+        let display = display as *mut x11_dl::xlib::Display;
+        let mut event_base = 0;
+        let mut error_base = 0;
+        if (xrandr.XRRQueryExtension)(display, &mut event_base, &mut error_base) == 0 {
+            eprintln!("The X server doesn't support the RandR extension");
+            return None;
+        }
+        (xrandr.XRRSelectInput)(display, root_window, RRScreenChangeNotifyMask);
+        Some(Self {
+            xrandr,
+            event_base,
+        })
+    }
+
+    pub unsafe fn update_configuration(&self, event: *mut x11::xlib::XEvent) {
+        (self.xrandr.XRRUpdateConfiguration)(event as *mut x11_dl::xlib::XEvent);
+    }
+
+    /// Returns the name and bounds of every connected output that is driven by a CRTC. Outputs that mirror
+    /// another output are only reported once.
+    pub unsafe fn query_outputs(&self, display: *mut Display, root_window: c_ulong) -> Vec<(String, Bounds)> {
+        let display = display as *mut x11_dl::xlib::Display;
+        let mut outputs: Vec<(String, Bounds)> = Vec::new();
+        let resources = (self.xrandr.XRRGetScreenResourcesCurrent)(display, root_window);
+        if resources.is_null() {
+            return outputs;
+        }
+        for i in 0..(*resources).noutput {
+            let output = *(*resources).outputs.offset(i as isize);
+            let info = (self.xrandr.XRRGetOutputInfo)(display, resources, output);
+            if info.is_null() {
+                continue;
+            }
+            if (*info).connection as c_int == RR_Connected && (*info).crtc != 0 {
+                let crtc = (self.xrandr.XRRGetCrtcInfo)(display, resources, (*info).crtc);
+                if !crtc.is_null() {
+                    let name = CStr::from_ptr((*info).name).to_string_lossy().into_owned();
+                    let bounds = Bounds::new((*crtc).x, (*crtc).y, (*crtc).width as c_int, (*crtc).height as c_int);
+                    if !outputs.iter().any(|(_, other)| *other == bounds) {
+                        outputs.push((name, bounds));
+                    }
+                    (self.xrandr.XRRFreeCrtcInfo)(crtc);
+                }
+            }
+            (self.xrandr.XRRFreeOutputInfo)(info);
+        }
+        (self.xrandr.XRRFreeScreenResources)(resources);
+        outputs.sort_by_key(|(_, bounds)| (bounds.x, bounds.y));
+        outputs
+    }
+}
+
+/// Determines the outputs to lay windows out on. Monitors set in the config take precedence, then the
+/// outputs reported by RandR. If neither is available the whole screen is used as a single output.
+pub unsafe fn detect_outputs(display: *mut Display, root_window: c_ulong, randr: Option<&RandR>, monitors: &[Monitor]) -> Vec<(String, Bounds)> {
+    if !monitors.is_empty() {
+        return monitors.iter()
+            .enumerate()
+            .map(|(index, monitor)| (format!("monitor-{}", index), monitor.bounds.clone()))
+            .collect();
+    }
+    if let Some(randr) = randr {
+        let outputs = randr.query_outputs(display, root_window);
+        if !outputs.is_empty() {
+            return outputs;
+        }
+    }
+    let screen = XDefaultScreen(display);
+    vec![("screen".to_string(), Bounds::new(0, 0, XDisplayWidth(display, screen), XDisplayHeight(display, screen)))]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outputs(bounds: &[Bounds]) -> Vec<Output> {
+        bounds.iter().enumerate().map(|(index, bounds)| Output::new(index.to_string(), bounds.clone(), index)).collect()
+    }
+
+    #[test]
+    fn neighbours_are_found_in_every_direction() {
+        // 0 1
+        //   2
+        let outputs = outputs(&[Bounds::new(0, 0, 1920, 1080), Bounds::new(1920, 0, 1920, 1080), Bounds::new(1920, 1080, 1920, 1080)]);
+        assert_eq!(find_neighbour(&outputs, 0, Direction::Right), Some(1));
+        assert_eq!(find_neighbour(&outputs, 1, Direction::Left), Some(0));
+        assert_eq!(find_neighbour(&outputs, 1, Direction::Down), Some(2));
+        assert_eq!(find_neighbour(&outputs, 2, Direction::Up), Some(1));
+        assert_eq!(find_neighbour(&outputs, 0, Direction::Left), None);
+        // Output 2 is below and to the right of output 0, but doesn't overlap with it
+        assert_eq!(find_neighbour(&outputs, 2, Direction::Left), None);
+        assert_eq!(find_neighbour(&outputs, 0, Direction::Down), None);
+    }
+
+    #[test]
+    fn closest_neighbour_wins() {
+        let outputs = outputs(&[Bounds::new(0, 0, 1000, 1000), Bounds::new(3000, 0, 1000, 1000), Bounds::new(1000, 500, 1000, 1000)]);
+        assert_eq!(find_neighbour(&outputs, 0, Direction::Right), Some(2));
+        assert_eq!(find_neighbour(&outputs, 2, Direction::Right), Some(1));
+    }
+}
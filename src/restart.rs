@@ -0,0 +1,84 @@
+use std::ffi::{c_int, c_ulong};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::layout::SavedLayout;
+use crate::util::{runtime_dir, Bounds};
+
+/// Everything a restarted window manager needs to put every window back where it was.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RestartState {
+    pub workspaces: Vec<WorkspaceState>,
+    /// The workspace shown on every output, by output name.
+    pub outputs: Vec<(String, usize)>,
+    pub focused_output: Option<String>,
+    /// Managed windows in the order they were mapped.
+    pub clients: Vec<ClientState>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WorkspaceState {
+    pub layout: SavedLayout,
+    pub floating: Vec<c_ulong>,
+    pub focused_floating: Option<c_ulong>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClientState {
+    pub window: c_ulong,
+    pub workspace: usize,
+    pub original_border_width: c_int,
+    #[serde(default)]
+    pub border_width: Option<c_int>,
+    pub floating: Option<Bounds>,
+    pub fullscreen: bool,
+}
+
+impl RestartState {
+    /// Returns the file the state is handed over in.
+    pub fn path() -> Result<PathBuf, String> {
+        let dir = runtime_dir().ok_or("XDG_RUNTIME_DIR isn't set")?;
+        Ok(dir.join(format!("xnebula-restart-{}.json", std::process::id())))
+    }
+
+    /// Writes the state to a new file that only the user can read. An existing file is never reused,
+    /// so that nobody else can plant a state.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let state = serde_json::to_string(self).map_err(|error| error.to_string())?;
+        let error = |error: std::io::Error| format!("Couldn't write {}: {}", path.display(), error);
+        let mut file = OpenOptions::new().write(true).create_new(true).mode(0o600).open(path).map_err(error)?;
+        file.write_all(state.as_bytes()).map_err(|write_error| {
+            let _ = std::fs::remove_file(path);
+            error(write_error)
+        })
+    }
+
+    /// Reads the state and removes the file, it is only needed once.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let state = std::fs::read_to_string(path).map_err(|error| format!("Couldn't read {}: {}", path.display(), error))?;
+        let _ = std::fs::remove_file(path);
+        serde_json::from_str(&state).map_err(|error| format!("Invalid restart state in {}: {}", path.display(), error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn state_is_only_written_to_new_private_files() {
+        let state = RestartState { workspaces: Vec::new(), outputs: vec![("HDMI-1".to_string(), 2)], focused_output: None, clients: Vec::new() };
+        let path = std::env::temp_dir().join(format!("xnebula-test-restart-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        state.save(&path).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert!(state.save(&path).is_err());
+
+        let loaded = RestartState::load(&path).unwrap();
+        assert_eq!(loaded.outputs, state.outputs);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,144 @@
+use std::ffi::c_int;
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer};
+use crate::criteria::WindowProperties;
+
+/// A regular expression that is compiled once when the config is loaded. It matches if it is found
+/// anywhere in the property, use `^` and `$` to match the whole value.
+#[derive(Debug, Clone)]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    pub fn is_match(&self, value: &str) -> bool {
+        self.0.is_match(value)
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern).map(Pattern).map_err(de::Error::custom)
+    }
+}
+
+/// Applies to every window mapped after the rule was loaded whose properties match all of the patterns
+/// that are set. A rule without patterns applies to every window.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rule {
+    pub class: Option<Pattern>,
+    pub instance: Option<Pattern>,
+    pub role: Option<Pattern>,
+    pub title: Option<Pattern>,
+    /// Numbered starting at 1 like in commands, stored starting at 0.
+    #[serde(deserialize_with = "deserialize_workspace")]
+    pub workspace: Option<usize>,
+    /// Name of the output whose workspace the window is put on.
+    pub output: Option<String>,
+    pub floating: Option<bool>,
+    /// Border width of the window, overriding the one from the `border` section.
+    pub border: Option<c_int>,
+    pub fullscreen: Option<bool>,
+    /// Whether the window gets focused when it is mapped.
+    pub focus: Option<bool>,
+}
+
+impl Rule {
+    pub fn matches(&self, properties: &WindowProperties) -> bool {
+        let matches = |pattern: &Option<Pattern>, value: &str| pattern.as_ref().is_none_or(|pattern| pattern.is_match(value));
+        matches(&self.class, &properties.class)
+            && matches(&self.instance, &properties.instance)
+            && matches(&self.role, &properties.role)
+            && matches(&self.title, &properties.title)
+    }
+}
+
+/// The combined effect of all rules that match a window. Later rules override earlier ones.
+#[derive(Debug, Default)]
+pub struct RuleEffects {
+    pub workspace: Option<usize>,
+    pub output: Option<String>,
+    pub floating: Option<bool>,
+    pub border: Option<c_int>,
+    pub fullscreen: Option<bool>,
+    pub focus: Option<bool>,
+}
+
+impl RuleEffects {
+    pub fn collect(rules: &[Rule], properties: &WindowProperties) -> Self {
+        let mut effects = Self::default();
+        for rule in rules.iter().filter(|rule| rule.matches(properties)) {
+            effects.workspace = rule.workspace.or(effects.workspace);
+            effects.output = rule.output.clone().or(effects.output);
+            effects.floating = rule.floating.or(effects.floating);
+            effects.border = rule.border.or(effects.border);
+            effects.fullscreen = rule.fullscreen.or(effects.fullscreen);
+            effects.focus = rule.focus.or(effects.focus);
+        }
+        effects
+    }
+}
+
+fn deserialize_workspace<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<usize>, D::Error> {
+    match usize::deserialize(deserializer)? {
+        0 => Err(de::Error::custom("workspaces are numbered starting at 1")),
+        workspace => Ok(Some(workspace - 1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<Vec<Rule>, toml::de::Error> {
+        #[derive(Deserialize)]
+        struct Rules {
+            rules: Vec<Rule>,
+        }
+        toml::from_str::<Rules>(source).map(|rules| rules.rules)
+    }
+
+    fn properties(class: &str, title: &str) -> WindowProperties {
+        WindowProperties { class: class.to_string(), title: title.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn patterns_match_anywhere_unless_anchored() {
+        let rules = parse(r#"
+            [[rules]]
+            class = "fox"
+            [[rules]]
+            class = "^fire"
+            title = "Private Browsing$"
+        "#).unwrap();
+        assert!(rules[0].matches(&properties("firefox", "")));
+        assert!(!rules[0].matches(&properties("FOX", "")));
+        assert!(rules[1].matches(&properties("firefox", "Mozilla Firefox Private Browsing")));
+        assert!(!rules[1].matches(&properties("firefox", "Private Browsing - Mozilla Firefox")));
+        assert!(Rule::default().matches(&WindowProperties::default()));
+    }
+
+    #[test]
+    fn later_rules_override_earlier_ones() {
+        let rules = parse(r#"
+            [[rules]]
+            workspace = 2
+            floating = true
+            [[rules]]
+            class = "mpv"
+            workspace = 4
+            focus = false
+        "#).unwrap();
+        let effects = RuleEffects::collect(&rules, &properties("mpv", ""));
+        assert_eq!((effects.workspace, effects.floating, effects.focus), (Some(3), Some(true), Some(false)));
+        let effects = RuleEffects::collect(&rules, &properties("xterm", ""));
+        assert_eq!((effects.workspace, effects.floating, effects.focus), (Some(1), Some(true), None));
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        for source in ["[[rules]]\nclass = \"(\"", "[[rules]]\nworkspace = 0", "[[rules]]\nname = \"a\""] {
+            assert!(parse(source).is_err(), "{}", source);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use std::ffi::c_int;
+use std::os::fd::RawFd;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// Write end of the pipe that the signal handler reports to.
+static WRITE_FD: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn on_signal(_signal: c_int) {
+    let fd = WRITE_FD.load(Ordering::SeqCst);
+    if fd >= 0 {
+        unsafe {
+            libc::write(fd, [1u8].as_ptr() as *const _, 1);
+        }
+    }
+}
+
+extern "C" fn on_child_exit(_signal: c_int) {
+    unsafe {
+        // waitpid may change errno, which the interrupted code might be about to read
+        let errno = *libc::__errno_location();
+        while libc::waitpid(-1, std::ptr::null_mut(), libc::WNOHANG) > 0 {}
+        *libc::__errno_location() = errno;
+    }
+}
+
+/// Reaps children started through `exec` as soon as they exit, so that they don't stay around as
+/// zombies. Unlike ignoring SIGCHLD, a handler isn't inherited by the children.
+pub unsafe fn reap_children() {
+    libc::signal(libc::SIGCHLD, on_child_exit as extern "C" fn(c_int) as libc::sighandler_t);
+}
+
+/// Turns SIGTERM and SIGINT into readable data on a pipe, so that the event loop can wait for them
+/// together with the X connection.
+pub struct SignalPipe {
+    read_fd: RawFd,
+}
+
+impl SignalPipe {
+    pub unsafe fn install() -> std::io::Result<Self> {
+        let mut fds = [0; 2];
+        if libc::pipe2(fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        WRITE_FD.store(fds[1], Ordering::SeqCst);
+        for signal in [libc::SIGTERM, libc::SIGINT] {
+            libc::signal(signal, on_signal as extern "C" fn(c_int) as libc::sighandler_t);
+        }
+        Ok(Self {
+            read_fd: fds[0],
+        })
+    }
+
+    pub fn fd(&self) -> RawFd {
+        self.read_fd
+    }
+
+    /// Returns whether a signal arrived since the last call.
+    pub fn received(&self) -> bool {
+        let mut buffer = [0u8; 64];
+        let mut received = false;
+        while unsafe { libc::read(self.read_fd, buffer.as_mut_ptr() as *mut _, buffer.len()) } > 0 {
+            received = true;
+        }
+        received
+    }
+}
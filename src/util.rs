@@ -1,4 +1,9 @@
-#[derive(Debug, PartialEq, Clone)]
+use std::path::PathBuf;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 #[repr(u8)]
 pub enum Direction {
     Left,
@@ -27,7 +32,21 @@ impl Direction {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left" => Ok(Direction::Left),
+            "right" => Ok(Direction::Right),
+            "up" => Ok(Direction::Up),
+            "down" => Ok(Direction::Down),
+            _ => Err(format!("invalid direction '{}', expected left, right, up or down", s)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Bounds {
     pub x: i32,
     pub y: i32,
@@ -105,4 +124,67 @@ impl Bounds {
             },
         }
     }
+
+    pub fn split_evenly(&self, direction: Direction, count: usize) -> Vec<Self> {
+        let mut parts = Vec::with_capacity(count);
+        let mut rest = self.clone();
+        for i in 0..count {
+            let (part, remaining) = rest.split(direction.clone(), 1.0 / (count - i) as f32);
+            parts.push(part);
+            rest = remaining;
+        }
+        parts
+    }
+}
+
+/// Returns `$XDG_RUNTIME_DIR`. There is no fallback, since only it is guaranteed to be private to the
+/// user, unlike the temp directory.
+pub fn runtime_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Replaces a leading `~` with the home directory, since paths in commands don't go through a shell.
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => PathBuf::from(home).join(rest.trim_start_matches('/')),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Splits a command line into words. Words can be quoted with single or double quotes to include
+/// whitespace.
+pub fn split_arguments(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => word.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_word = true;
+            }
+            None if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            None => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if let Some(q) = quote {
+        return Err(format!("unterminated {} quote", q));
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
 }
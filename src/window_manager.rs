@@ -1,61 +1,307 @@
-use std::ffi::{c_int, c_uint, c_ulong};
-use x11::keysym::{XK_Down, XK_e, XK_Left, XK_q, XK_Return, XK_Right, XK_Up};
-use x11::xlib::{ControlMask, CurrentTime, CWBorderWidth, CWHeight, CWWidth, CWX, CWY, Display, False, GrabModeAsync, LockMask, Mod1Mask, Mod2Mask, Mod4Mask, RevertToNone, ShiftMask, SubstructureNotifyMask, SubstructureRedirectMask, XCloseDisplay, XConfigureEvent, XConfigureRequestEvent, XConfigureWindow, XCreateWindowEvent, XDefaultScreen, XDestroyWindowEvent, XErrorEvent, XEvent, XGetWindowAttributes, XGrabKey, XKeyEvent, XKeymapEvent, XKeysymToKeycode, XKillClient, XMapEvent, XMappingEvent, XMapRequestEvent, XMapWindow, XNextEvent, XOpenDisplay, XReparentEvent, XRootWindow, XSelectInput, XSetErrorHandler, XSetInputFocus, XSetWindowBorder, XUnmapEvent, XWindowAttributes, XWindowChanges};
+use std::collections::HashMap;
+use std::ffi::{c_char, c_int, c_long, c_uint, c_ulong, CStr, CString};
+use std::fmt::{Display as FmtDisplay, Formatter};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use serde_json::{json, Value};
+use x11_dl::xrandr::RRScreenChangeNotify;
+use x11::xlib::{AnyModifier, BadAccess, BadMatch, BadWindow, Button1, Button3, ButtonPressMask, ButtonReleaseMask, ClientMessage, XClientMessageEvent, ControlMask, CurrentTime, CWBorderWidth, CWHeight, CWWidth, CWX, CWY, Display, False, GrabModeAsync, IsViewable, LockMask, Mod1Mask, Mod2Mask, Mod3Mask, Mod4Mask, Mod5Mask, NoEventMask, PointerMotionMask, PointerRoot, PropertyChangeMask, RevertToNone, RevertToPointerRoot, ShiftMask, SubstructureNotifyMask, StructureNotifyMask, SubstructureRedirectMask, XCloseDisplay, XButtonEvent, XConfigureEvent, XConfigureRequestEvent, XConfigureWindow, XConnectionNumber, XDeleteProperty, XEventsQueued, XFlush, XCreateWindowEvent, XCheckTypedWindowEvent, XDefaultScreen, XDestroyWindow, XDestroyWindowEvent, XDisplayHeight, XDisplayWidth, XErrorEvent, XEvent, XFree, XGetErrorDatabaseText, XGetErrorText, XGetWindowAttributes, XGetSelectionOwner, XGetTransientForHint, XGetWMHints, XGetWMProtocols, XGrabButton, XGrabKey, XKeyEvent, XUngrabButton, XKeymapEvent, XKeysymToKeycode, XKillClient, XMapEvent, XMappingEvent, XMapRequestEvent, XMapWindow, XMotionEvent, XNextEvent, XOpenDisplay, XPending, XPropertyEvent, XQueryTree, XRaiseWindow, XReparentEvent, XSelectionClearEvent, XRootWindow, XSelectInput, XSendEvent, XSync, XSetErrorHandler, XSetIOErrorHandler, XSetInputFocus, XSetSelectionOwner, XSetWindowBorder, XUngrabKey, XUnmapEvent, XUnmapWindow, XUrgencyHint, XWindowAttributes, XWindowChanges, XA_WM_HINTS};
 use crate::action::{Action};
-use crate::config::{Config, Monitor};
+use crate::atoms::Atoms;
+use crate::client::Client;
+use crate::colors::BorderColors;
+use crate::config::{Config, ConfigError};
+use crate::criteria::{Criteria, WindowProperties};
+use crate::ewmh;
+use crate::ewmh::Strut;
+use crate::ipc::{ConnectionId, Event, IpcServer};
 use crate::keybind::Keybind;
-use crate::layout::{ChangedWindows, Window, WindowTree};
-use crate::util::Direction;
+use crate::layout::{create_layout, restore_layout, ChangedWindows, GapKind, Layout, LayoutKind, Window, WindowTree};
+use crate::output::{detect_outputs, find_neighbour, Output, RandR};
+use crate::restart::{ClientState, RestartState, WorkspaceState};
+use crate::rules::RuleEffects;
+use crate::signals;
+use crate::signals::SignalPipe;
+use crate::util::{split_arguments, Bounds, Direction};
+use crate::workspace::Workspace;
 
-extern "C" fn custom_error_handler(_display: *mut Display, error_event: *mut XErrorEvent) -> c_int {
-    println!("X11 Error occurred: {:?}", error_event);
+/// `QueuedAlready` from Xlib.h, missing in the x11 crate. Makes `XEventsQueued` only count the events
+/// that Xlib already read from the connection.
+const QUEUED_ALREADY: c_int = 0;
+
+/// Windows that caused a BadWindow or BadMatch error. They have probably been destroyed and are dropped
+/// by the event loop if they really are gone.
+static FAILED_WINDOWS: Mutex<Vec<c_ulong>> = Mutex::new(Vec::new());
+
+/// The IPC socket, removed by the XIO error handler because it exits the process without unwinding.
+static IPC_SOCKET: OnceLock<PathBuf> = OnceLock::new();
+
+extern "C" fn custom_error_handler(display: *mut Display, error_event: *mut XErrorEvent) -> c_int {
+    let error = unsafe { &*error_event };
+    if error.error_code == BadWindow || error.error_code == BadMatch {
+        FAILED_WINDOWS.lock().unwrap().push(error.resourceid);
+        return 0;
+    }
+
+    let mut text = [0 as c_char; 256];
+    let mut request = [0 as c_char; 256];
+    unsafe {
+        XGetErrorText(display, error.error_code as c_int, text.as_mut_ptr(), text.len() as c_int);
+        let request_code = CString::new(error.request_code.to_string()).unwrap();
+        XGetErrorDatabaseText(display, c"XRequest".as_ptr(), request_code.as_ptr(), c"".as_ptr(), request.as_mut_ptr(), request.len() as c_int);
+    }
+    let text = unsafe { CStr::from_ptr(text.as_ptr()) }.to_string_lossy();
+    let request = unsafe { CStr::from_ptr(request.as_ptr()) }.to_string_lossy();
+    eprintln!("X error: {} (request: {} {}.{}, resource: {:#x})", text, request, error.request_code, error.minor_code, error.resourceid);
     0
 }
 
+/// Called when the connection to the X server is lost. Xlib terminates the process once this returns.
+extern "C" fn io_error_handler(_display: *mut Display) -> c_int {
+    eprintln!("Lost the connection to the X server, exiting");
+    if let Some(path) = IPC_SOCKET.get() {
+        let _ = std::fs::remove_file(path);
+    }
+    std::process::exit(1);
+}
+
+static REDIRECT_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Only one client can select SubstructureRedirect on the root window, everybody else gets BadAccess.
+extern "C" fn redirect_error_handler(_display: *mut Display, error_event: *mut XErrorEvent) -> c_int {
+    if unsafe { (*error_event).error_code } == BadAccess {
+        REDIRECT_FAILED.store(true, Ordering::SeqCst);
+    }
+    0
+}
+
+/// How long the window manager that is being replaced gets to exit.
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub enum StartupError {
+    Config(ConfigError),
+    NoDisplay,
+    OtherWindowManager {
+        replaceable: bool,
+    },
+    ReplaceTimeout,
+}
+
+impl From<ConfigError> for StartupError {
+    fn from(error: ConfigError) -> Self {
+        StartupError::Config(error)
+    }
+}
+
+impl FmtDisplay for StartupError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StartupError::Config(error) => write!(f, "{}", error),
+            StartupError::NoDisplay => write!(f, "Failed to open X display"),
+            StartupError::OtherWindowManager { replaceable: true } => write!(f, "Another window manager is already running, use --replace to replace it"),
+            StartupError::OtherWindowManager { replaceable: false } => write!(f, "Another window manager is already running and it can't be replaced"),
+            StartupError::ReplaceTimeout => write!(f, "The running window manager didn't exit within {} seconds", REPLACE_TIMEOUT.as_secs()),
+        }
+    }
+}
+
+/// Takes over the `WM_S<n>` selection and SubstructureRedirect on the root window. If `replace` is set, a
+/// running window manager is asked to exit through the selection, otherwise its presence is an error.
+unsafe fn become_window_manager(display: *mut Display, root_window: c_ulong, check_window: c_ulong, atoms: &Atoms, replace: bool) -> Result<(), StartupError> {
+    let old_owner = XGetSelectionOwner(display, atoms.wm_selection);
+    if old_owner != 0 {
+        if !replace {
+            return Err(StartupError::OtherWindowManager { replaceable: true });
+        }
+        XSelectInput(display, old_owner, StructureNotifyMask);
+    }
+    XSetSelectionOwner(display, atoms.wm_selection, check_window, CurrentTime);
+    if XGetSelectionOwner(display, atoms.wm_selection) != check_window {
+        return Err(StartupError::OtherWindowManager { replaceable: old_owner != 0 });
+    }
+
+    let deadline = Instant::now() + REPLACE_TIMEOUT;
+    if old_owner != 0 {
+        // The old window manager destroys its selection window once it gave up. If it was already gone
+        // before we selected its events, no DestroyNotify arrives.
+        let mut event: XEvent = std::mem::zeroed();
+        let mut attributes: XWindowAttributes = std::mem::zeroed();
+        while XCheckTypedWindowEvent(display, old_owner, x11::xlib::DestroyNotify, &mut event) == 0 && XGetWindowAttributes(display, old_owner, &mut attributes) != 0 {
+            if Instant::now() >= deadline {
+                return Err(StartupError::ReplaceTimeout);
+            }
+            std::thread::sleep(Duration::from_millis(50));
+            XSync(display, False);
+        }
+    }
+
+    let mut event: XEvent = std::mem::zeroed();
+    event.client_message.type_ = ClientMessage;
+    event.client_message.window = root_window;
+    event.client_message.message_type = atoms.manager;
+    event.client_message.format = 32;
+    event.client_message.data.set_long(0, CurrentTime as c_long);
+    event.client_message.data.set_long(1, atoms.wm_selection as c_long);
+    event.client_message.data.set_long(2, check_window as c_long);
+    XSendEvent(display, root_window, False, StructureNotifyMask, &mut event);
+
+    // Window managers that don't use the selection still hold the redirect
+    loop {
+        REDIRECT_FAILED.store(false, Ordering::SeqCst);
+        XSetErrorHandler(Some(redirect_error_handler));
+        XSelectInput(display, root_window, SubstructureRedirectMask | SubstructureNotifyMask);
+        XSync(display, False);
+        XSetErrorHandler(Some(custom_error_handler));
+        if !REDIRECT_FAILED.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        if old_owner == 0 {
+            return Err(StartupError::OtherWindowManager { replaceable: false });
+        }
+        if Instant::now() >= deadline {
+            return Err(StartupError::ReplaceTimeout);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// A floating window that is being moved or resized with the mouse.
+struct Drag {
+    window_id: c_ulong,
+    resize: bool,
+    pointer_x: c_int,
+    pointer_y: c_int,
+    bounds: Bounds,
+}
+
+const MIN_FLOATING_SIZE: c_int = 32;
+
 pub struct WindowManager {
     config: Config,
     display: *mut Display,
     root_window: c_ulong,
     keybinds: Vec<Keybind>,
-    layout: WindowTree,
+    workspaces: Vec<Workspace>,
+    outputs: Vec<Output>,
+    focused_output: usize,
+    randr: Option<RandR>,
+    clients: HashMap<c_ulong, Client>,
+    /// `None` if borders are disabled.
+    border_colors: Option<BorderColors>,
+    atoms: Atoms,
+    drag: Option<Drag>,
+    ipc: Option<IpcServer>,
+    /// The window that last got the input focus, the root window if none did.
+    input_focus: c_ulong,
+    /// Managed windows in the order they were mapped, for `_NET_CLIENT_LIST`.
+    client_list: Vec<c_ulong>,
+    check_window: c_ulong,
+    /// Docks and panels. They aren't managed, but the space they reserve is kept free of windows.
+    docks: HashMap<c_ulong, Option<Strut>>,
+    signals: Option<SignalPipe>,
+    /// Cleared to leave the event loop.
+    running: bool,
+    /// Where the state was saved to if the window manager is restarting.
+    restart_state: Option<PathBuf>,
 }
 
 impl WindowManager {
-    pub unsafe fn new() -> Self {
-        let monitors = vec![
-            Monitor::new(2560, 1440, 0, 0),
-        ];
-        let config = Config {
-            monitors,
-            ..Config::default()
-        };
-        assert!(config.monitors.len() > 0);
-        println!("Config: {:?}", config);
+    /// Connects to the X server and takes over the screen. With `replace`, a running window manager is
+    /// replaced instead of making startup fail.
+    pub unsafe fn new(replace: bool) -> Result<Self, StartupError> {
+        let config = Config::load()?;
+        if config.debug_events {
+            println!("Config: {:?}", config);
+        }
 
         XSetErrorHandler(Some(custom_error_handler));
+        XSetIOErrorHandler(Some(io_error_handler));
 
         let display = XOpenDisplay(std::ptr::null());
         if display.is_null() {
-            eprintln!("Failed to open X display");
+            return Err(StartupError::NoDisplay);
         }
 
         let screen = XDefaultScreen(display);
         let root_window = XRootWindow(display, screen);
 
         let keybinds = Vec::new();
+        let border_colors = config.border.as_ref().map(|border| BorderColors::allocate(display, border));
+        let atoms = Atoms::new(display);
+        let check_window = ewmh::create_check_window(display, root_window);
+        if let Err(error) = become_window_manager(display, root_window, check_window, &atoms, replace) {
+            XCloseDisplay(display);
+            return Err(error);
+        }
+        ewmh::init(display, root_window, check_window, &atoms);
+
+        let ipc = match IpcServer::bind() {
+            Ok(ipc) => {
+                println!("IPC socket: {}", ipc.path().display());
+                let _ = IPC_SOCKET.set(ipc.path().clone());
+                Some(ipc)
+            }
+            Err(error) => {
+                eprintln!("Couldn't create IPC socket: {}", error);
+                None
+            }
+        };
+
+        signals::reap_children();
+        let signals = match SignalPipe::install() {
+            Ok(signals) => Some(signals),
+            Err(error) => {
+                eprintln!("Couldn't install signal handlers: {}", error);
+                None
+            }
+        };
+
+        let randr = RandR::init(display, root_window);
+        let outputs: Vec<Output> = detect_outputs(display, root_window, randr.as_ref(), &config.monitors)
+            .into_iter()
+            .enumerate()
+            .map(|(index, (name, bounds))| Output::new(name, bounds, index))
+            .collect();
+        println!("Outputs: {:?}", outputs);
 
-        let tree = WindowTree::new(config.monitors[0].bounds.clone());
+        let workspaces = (0..config.workspace_count.max(outputs.len()))
+            .map(|index| {
+                let bounds = outputs.get(index).unwrap_or(&outputs[0]).bounds.clone();
+                Workspace::new(create_layout(config.layout.clone(), bounds, &config))
+            })
+            .collect();
 
-        Self {
+        Ok(Self {
             config,
             display,
             root_window,
             keybinds,
-            layout: tree,
-        }
+            workspaces,
+            outputs,
+            focused_output: 0,
+            randr,
+            clients: HashMap::new(),
+            border_colors,
+            atoms,
+            drag: None,
+            ipc,
+            input_focus: root_window,
+            client_list: Vec::new(),
+            check_window,
+            docks: HashMap::new(),
+            signals,
+            running: true,
+            restart_state: None,
+        })
     }
 
-    pub unsafe fn run(&mut self) {
+    /// Runs the event loop until the window manager is told to exit, then gives the windows back.
+    pub unsafe fn run(&mut self) -> ExitCode {
         let mut window_attributes: XWindowAttributes = XWindowAttributes {
             x: 0,
             y: 0,
@@ -86,79 +332,267 @@ impl WindowManager {
         println!("Screen size: {}x{}", window_attributes.width, window_attributes.height);
         println!("Root window ID: {}", self.root_window);
 
-        XSelectInput(self.display, self.root_window, SubstructureRedirectMask | SubstructureNotifyMask);
-
-        self.register_keybind(XK_e, Mod4Mask, Action::Exit);
-        self.register_keybind(XK_Return, Mod4Mask, Action::ExecuteCommand { command: "alacritty".to_string() });
-        self.register_keybind(XK_Left, Mod4Mask, Action::MoveFocus { direction: Direction::Left });
-        self.register_keybind(XK_Right, Mod4Mask, Action::MoveFocus { direction: Direction::Right });
-        self.register_keybind(XK_Up, Mod4Mask, Action::MoveFocus { direction: Direction::Up });
-        self.register_keybind(XK_Down, Mod4Mask, Action::MoveFocus { direction: Direction::Down });
-        self.register_keybind(XK_q, Mod4Mask | ShiftMask, Action::CloseFocusedWindow);
-        self.register_keybind(XK_Left, Mod4Mask | Mod1Mask, Action::ChangeTilingDirection { direction: Direction::Left });
-        self.register_keybind(XK_Right, Mod4Mask | Mod1Mask, Action::ChangeTilingDirection { direction: Direction::Right });
-        self.register_keybind(XK_Up, Mod4Mask | Mod1Mask, Action::ChangeTilingDirection { direction: Direction::Up });
-        self.register_keybind(XK_Down, Mod4Mask | Mod1Mask, Action::ChangeTilingDirection { direction: Direction::Down });
-        self.register_keybind(XK_Left, Mod4Mask | ControlMask, Action::ResizeFocusedWindow { direction: Direction::Left, amount: 0.1 });
-        self.register_keybind(XK_Right, Mod4Mask | ControlMask, Action::ResizeFocusedWindow { direction: Direction::Right, amount: 0.1 });
-        self.register_keybind(XK_Up, Mod4Mask | ControlMask, Action::ResizeFocusedWindow { direction: Direction::Up, amount: 0.1 });
-        self.register_keybind(XK_Down, Mod4Mask | ControlMask, Action::ResizeFocusedWindow { direction: Direction::Down, amount: 0.1 });
-
-        loop {
-            let mut event: XEvent = std::mem::zeroed();
-            let result = XNextEvent(self.display, &mut event);
-            //println!("Event received: type={}", event.get_type());
-            if result != 0 {
-                eprintln!("Error on XNextEvent: {}", result);
-            }
-
-            match event.get_type() {
-                x11::xlib::CreateNotify => {
-                    self.on_create_notify(event.create_window);
-                }
-                x11::xlib::ConfigureRequest => {
-                    self.on_configure_request(event.configure_request);
-                }
-                x11::xlib::ConfigureNotify => {
-                    self.on_configure_notify(event.configure);
-                }
-                x11::xlib::MapRequest => {
-                    self.on_map_request(event.map_request);
-                }
-                x11::xlib::MapNotify => {
-                    self.on_map_notify(event.map);
-                }
-                x11::xlib::UnmapNotify => {
-                    self.on_unmap_notify(event.unmap);
-                }
-                x11::xlib::DestroyNotify => {
-                    self.on_destroy_notify(event.destroy_window);
-                }
-                x11::xlib::ReparentNotify => {
-                    self.on_reparent_notify(event.reparent);
-                }
-                x11::xlib::KeymapNotify => {
-                    self.on_keymap_notify(event.keymap);
-                }
-                x11::xlib::MappingNotify => {
-                    self.on_mapping_notify(event.mapping);
-                }
-                x11::xlib::KeyPress => {
-                    self.on_keypress(event.key);
-                }
-                x11::xlib::KeyRelease => {
-                    // let event = event.key;
-                    // println!("KeyRelease: {{ keycode: {}, state: {} }}", event.keycode, event.state);
+        self.register_keybinds();
+        self.grab_button(Button1, Mod4Mask);
+        self.grab_button(Button3, Mod4Mask);
+        self.update_desktops();
+        self.adopt_windows();
+        self.update_client_list();
+        self.focus_current_workspace();
+
+        while self.running {
+            while self.running && XPending(self.display) > 0 {
+                let mut event: XEvent = std::mem::zeroed();
+                let result = XNextEvent(self.display, &mut event);
+                //println!("Event received: type={}", event.get_type());
+                if result != 0 {
+                    eprintln!("Error on XNextEvent: {}", result);
                 }
-                _ => {
-                    // let atom_value = 367;
-                    // let atom_name_ptr = XGetAtomName(display, atom_value);
-                    // let atom_name = std::ffi::CStr::from_ptr(atom_name_ptr).to_string_lossy();
-                    // println!("Atom {} has name: {}", atom_value, atom_name);
-                    // XFree(atom_name_ptr as *mut _);
-                    if self.config.debug_events {
-                        println!("Other: {:?}", event);
-                    }
+                self.handle_event(&mut event);
+            }
+            self.handle_ipc_requests();
+            if self.signals.as_ref().is_some_and(SignalPipe::received) {
+                println!("Received a termination signal");
+                self.exit();
+            }
+            if !self.running {
+                break;
+            }
+            self.kill_unresponsive_clients();
+            self.drop_failed_windows();
+            self.wait_for_events();
+        }
+
+        if let Some(path) = self.restart_state.take() {
+            return self.exec_restart(&path);
+        }
+        self.shutdown();
+        ExitCode::SUCCESS
+    }
+
+    /// Blocks until the X server or an IPC client sends something or the next close timeout expires.
+    unsafe fn wait_for_events(&self) {
+        // Requests made since the last round trip, like killing a client, are still in Xlib's buffer
+        XFlush(self.display);
+        // Round trips made after the event loop checked for events can have queued new ones, which poll
+        // wouldn't report
+        if XEventsQueued(self.display, QUEUED_ALREADY) > 0 {
+            return;
+        }
+        let timeout = match self.clients.values().filter_map(|client| client.kill_deadline).min() {
+            Some(deadline) => deadline.saturating_duration_since(Instant::now()).as_millis().min(c_int::MAX as u128) as c_int,
+            None => -1,
+        };
+        let mut fds = vec![XConnectionNumber(self.display)];
+        if let Some(signals) = &self.signals {
+            fds.push(signals.fd());
+        }
+        let mut fds: Vec<libc::pollfd> = fds.into_iter()
+            .map(|fd| libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            })
+            .collect();
+        if let Some(ipc) = &self.ipc {
+            fds.extend(ipc.poll_fds());
+        }
+        libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout);
+    }
+
+    unsafe fn handle_ipc_requests(&mut self) {
+        let Some(ipc) = &mut self.ipc else {
+            return;
+        };
+        for (connection, request) in ipc.receive() {
+            let reply = self.handle_ipc_request(connection, &request);
+            // Most commands make no round trip, send their requests before the client hears back
+            XFlush(self.display);
+            if let Some(ipc) = &mut self.ipc {
+                ipc.send(connection, &reply);
+            }
+        }
+    }
+
+    fn handle_ipc_request(&mut self, connection: ConnectionId, request: &str) -> Value {
+        if self.config.debug_events {
+            println!("IPC Request: {}", request);
+        }
+        if let Some(events) = request.strip_prefix("subscribe").filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace)) {
+            let events = events.split_whitespace().map(str::to_string).collect();
+            let Some(ipc) = &mut self.ipc else {
+                return json!({ "success": false, "error": "IPC is not available" });
+            };
+            return match ipc.subscribe(connection, events) {
+                Ok(()) => json!({ "success": true }),
+                Err(error) => json!({ "success": false, "error": error }),
+            };
+        }
+        if let Some(result) = self.query(request) {
+            return match result {
+                Ok(result) => json!({ "success": true, "result": result }),
+                Err(error) => json!({ "success": false, "error": error }),
+            };
+        }
+        match request.parse::<Action>() {
+            Ok(action) => match action.execute(self) {
+                Ok(()) => json!({ "success": true }),
+                Err(error) => json!({ "success": false, "error": error }),
+            },
+            Err(error) => json!({ "success": false, "error": error }),
+        }
+    }
+
+    /// Sends an event to the IPC clients that subscribed to it.
+    fn emit(&mut self, event: Event) {
+        if self.config.debug_events {
+            println!("Event: {:?}", event);
+        }
+        if let Some(ipc) = &mut self.ipc {
+            ipc.broadcast(&event);
+        }
+    }
+
+    /// Answers read-only requests about the state of the window manager. Returns `None` if the request
+    /// isn't a query.
+    fn query(&self, request: &str) -> Option<Result<Value, String>> {
+        let words = match split_arguments(request) {
+            Ok(words) => words,
+            Err(error) => return Some(Err(error)),
+        };
+        let (name, args) = words.split_first()?;
+        let result = match name.as_str() {
+            "get-tree" => match args {
+                [] => Ok(self.dump_workspace(self.current_workspace())),
+                [workspace] => match workspace.parse::<usize>() {
+                    Ok(workspace) if (1..=self.workspaces.len()).contains(&workspace) => Ok(self.dump_workspace(workspace - 1)),
+                    _ => Err(format!("invalid workspace '{}'", workspace)),
+                },
+                _ => Err("usage: get-tree [workspace]".to_string()),
+            },
+            "get-workspaces" | "get-windows" | "get-outputs" | "get-focused" if !args.is_empty() => Err(format!("'{}' doesn't take any arguments", name)),
+            "get-workspaces" => Ok(Value::from_iter((0..self.workspaces.len()).map(|workspace| self.dump_workspace(workspace)))),
+            "get-windows" => Ok(self.dump_windows()),
+            "get-outputs" => Ok(self.dump_outputs()),
+            "get-focused" => Ok(json!({
+                "window": self.focused_window(),
+                "workspace": self.current_workspace() + 1,
+                "output": self.outputs[self.focused_output].name,
+            })),
+            _ => return None,
+        };
+        Some(result)
+    }
+
+    fn dump_workspace(&self, workspace: usize) -> Value {
+        let output = self.outputs.iter().position(|output| output.workspace == workspace);
+        json!({
+            "number": workspace + 1,
+            "output": output.map(|output| &self.outputs[output].name),
+            "focused": output == Some(self.focused_output),
+            "tiled": self.workspaces[workspace].layout.dump(),
+            "floating": self.workspaces[workspace].floating,
+            "focused_floating": self.workspaces[workspace].focused_floating,
+        })
+    }
+
+    fn dump_windows(&self) -> Value {
+        let focused_window = self.focused_window();
+        let mut windows: Vec<(&c_ulong, &Client)> = self.clients.iter().collect();
+        windows.sort_by_key(|(window_id, _)| **window_id);
+        Value::from_iter(windows.into_iter().map(|(window_id, client)| json!({
+            "id": window_id,
+            "workspace": client.workspace + 1,
+            "floating": client.floating,
+            "visible": self.is_visible(client.workspace),
+            "focused": focused_window == Some(*window_id),
+        })))
+    }
+
+    fn dump_outputs(&self) -> Value {
+        let outputs = Value::from_iter(self.outputs.iter().enumerate().map(|(index, output)| json!({
+            "name": output.name,
+            "bounds": output.bounds,
+            "workspace": output.workspace + 1,
+            "focused": index == self.focused_output,
+        })));
+        json!({
+            "outputs": outputs,
+            "configured_monitors": self.config.monitors,
+        })
+    }
+
+    unsafe fn handle_event(&mut self, event: &mut XEvent) {
+        let is_screen_change = self.randr.as_ref().is_some_and(|randr| event.get_type() == randr.event_base + RRScreenChangeNotify);
+        if is_screen_change {
+            self.on_screen_change_notify(event);
+            return;
+        }
+
+        match event.get_type() {
+            x11::xlib::CreateNotify => {
+                self.on_create_notify(event.create_window);
+            }
+            x11::xlib::ConfigureRequest => {
+                self.on_configure_request(event.configure_request);
+            }
+            x11::xlib::ConfigureNotify => {
+                self.on_configure_notify(event.configure);
+            }
+            x11::xlib::MapRequest => {
+                self.on_map_request(event.map_request);
+            }
+            x11::xlib::MapNotify => {
+                self.on_map_notify(event.map);
+            }
+            x11::xlib::UnmapNotify => {
+                self.on_unmap_notify(event.unmap);
+            }
+            x11::xlib::DestroyNotify => {
+                self.on_destroy_notify(event.destroy_window);
+            }
+            x11::xlib::ReparentNotify => {
+                self.on_reparent_notify(event.reparent);
+            }
+            x11::xlib::KeymapNotify => {
+                self.on_keymap_notify(event.keymap);
+            }
+            x11::xlib::MappingNotify => {
+                self.on_mapping_notify(event.mapping);
+            }
+            x11::xlib::PropertyNotify => {
+                self.on_property_notify(event.property);
+            }
+            x11::xlib::SelectionClear => {
+                self.on_selection_clear(event.selection_clear);
+            }
+            x11::xlib::ClientMessage => {
+                self.on_client_message(event.client_message);
+            }
+            x11::xlib::KeyPress => {
+                self.on_keypress(event.key);
+            }
+            x11::xlib::ButtonPress => {
+                self.on_button_press(event.button);
+            }
+            x11::xlib::ButtonRelease => {
+                self.on_button_release(event.button);
+            }
+            x11::xlib::MotionNotify => {
+                self.on_motion_notify(event.motion);
+            }
+            x11::xlib::KeyRelease => {
+                // let event = event.key;
+                // println!("KeyRelease: {{ keycode: {}, state: {} }}", event.keycode, event.state);
+            }
+            _ => {
+                // let atom_value = 367;
+                // let atom_name_ptr = XGetAtomName(display, atom_value);
+                // let atom_name = std::ffi::CStr::from_ptr(atom_name_ptr).to_string_lossy();
+                // println!("Atom {} has name: {}", atom_value, atom_name);
+                // XFree(atom_name_ptr as *mut _);
+                if self.config.debug_events {
+                    println!("Other: {:?}", event);
                 }
             }
         }
@@ -198,15 +632,167 @@ impl WindowManager {
             println!("Map Request: {}", request.window);
         }
 
-        let changed = self.layout.insert(Window::new(request.window));
-        self.configure_changed_windows(changed);
+        if self.clients.contains_key(&request.window) || self.docks.contains_key(&request.window) {
+            XMapWindow(self.display, request.window);
+            return;
+        }
+        let mut attributes: XWindowAttributes = std::mem::zeroed();
+        if XGetWindowAttributes(self.display, request.window, &mut attributes) == 0 || attributes.override_redirect != 0 {
+            return;
+        }
+        self.manage_window(request.window, &attributes, None);
+    }
+
+    /// Tracks a window as a client, both for newly managed windows and for those restored after a
+    /// restart.
+    unsafe fn add_client(&mut self, window_id: c_ulong, client: Client) {
+        self.clients.insert(window_id, client);
+        self.client_list.push(window_id);
+        XSelectInput(self.display, window_id, PropertyChangeMask);
+        self.read_urgency_hint(window_id);
+    }
+
+    /// Starts managing a window. Docks only get their space reserved, all other windows are put into
+    /// `workspace` or, if that is `None`, the one chosen by the rules. Without either, tiled windows go
+    /// into a matching placeholder on any workspace, and everything else into the focused one.
+    unsafe fn manage_window(&mut self, window_id: c_ulong, attributes: &XWindowAttributes, workspace: Option<usize>) {
+        let window_types = ewmh::get_longs(self.display, window_id, self.atoms.net_wm_window_type);
+        if window_types.contains(&self.atoms.net_wm_window_type_dock) {
+            self.manage_dock(window_id);
+            return;
+        }
 
-        if let Some(border) = &self.config.border {
-            XSetWindowBorder(self.display, request.window, border.color);
+        let properties = WindowProperties::read(self.display, window_id, &self.atoms);
+        let rules = RuleEffects::collect(&self.config.rules, &properties);
+        let rule_workspace = rules.workspace
+            .filter(|workspace| *workspace < self.workspaces.len())
+            .or_else(|| {
+                let output = rules.output.as_ref()?;
+                self.outputs.iter().find(|candidate| candidate.name == *output).map(|output| output.workspace)
+            });
+        let workspace = workspace.or(rule_workspace);
+
+        let mut parent = 0;
+        let is_transient = XGetTransientForHint(self.display, window_id, &mut parent) != 0;
+        let parent = Some(parent).filter(|parent| is_transient && self.clients.contains_key(parent));
+        // Transient windows open next to their parent if it is visible
+        if let Some(output) = parent.and_then(|parent| self.outputs.iter().position(|output| output.workspace == self.clients[&parent].workspace)) {
+            if workspace.is_none() {
+                self.focused_output = output;
+            }
+        }
+        let chosen_workspace = workspace;
+        let workspace = workspace.unwrap_or_else(|| self.current_workspace());
+        let floating_types = [self.atoms.net_wm_window_type_dialog, self.atoms.net_wm_window_type_utility, self.atoms.net_wm_window_type_splash, self.atoms.net_wm_window_type_notification];
+        let mut client = Client::new(workspace);
+        client.original_border_width = attributes.border_width;
+        client.border_width = rules.border;
+        let floats_by_type = is_transient || window_types.iter().any(|window_type| floating_types.contains(window_type));
+        if rules.floating.unwrap_or(floats_by_type) {
+            client.floating = Some(self.centered_bounds(attributes, parent));
+        }
+        let floating = client.floating.clone();
+        self.add_client(window_id, client);
+
+        // Rules with `focus = false` leave the focus of the workspace that the window lands on as it was
+        let previous_focus: Vec<_> = self.workspaces.iter().map(|workspace| (workspace.layout.focused_window(), workspace.focused_floating)).collect();
+        self.update_border(window_id);
+        let workspace = match floating.is_none().then(|| self.swallow_window(window_id, &properties, chosen_workspace)).flatten() {
+            Some(workspace) => workspace,
+            None => {
+                self.attach_window(window_id, workspace);
+                workspace
+            }
+        };
+        if let Some(bounds) = &floating {
+            self.configure_window(window_id, bounds);
         }
 
-        XMapWindow(self.display, request.window);
-        XSetInputFocus(self.display, request.window, RevertToNone, CurrentTime);
+        let requests_fullscreen = ewmh::get_longs(self.display, window_id, self.atoms.net_wm_state).contains(&self.atoms.net_wm_state_fullscreen);
+        if rules.fullscreen.unwrap_or(requests_fullscreen) {
+            self.set_fullscreen(window_id, true);
+        }
+        self.update_state(window_id);
+
+        if self.is_visible(workspace) {
+            XMapWindow(self.display, window_id);
+            self.raise_floating_windows(workspace);
+        } else if attributes.map_state == IsViewable {
+            // Windows that were adopted are already mapped, new ones simply aren't mapped yet
+            self.unmap_window(window_id);
+        }
+        self.emit(Event::WindowManaged { window: window_id, workspace: workspace + 1 });
+        if rules.focus == Some(false) {
+            // Give the focus back to the windows that had it, the layout moved it to the new window
+            let (tiled, floating) = previous_focus[workspace];
+            if let Some(tiled) = tiled {
+                self.workspaces[workspace].layout.focus_window(tiled);
+            }
+            self.workspaces[workspace].focused_floating = floating;
+            if workspace == self.current_workspace() && self.clients.contains_key(&self.input_focus) {
+                self.focus_window(self.input_focus);
+            }
+            return;
+        }
+        if workspace != self.current_workspace() {
+            return;
+        }
+        if floating.is_some() {
+            self.focus_floating_window(window_id);
+        } else {
+            self.set_input_focus(window_id);
+        }
+    }
+
+    /// Manages the windows that were already mapped before the window manager started. Windows that were
+    /// moved to a workspace by a previous instance go back there.
+    unsafe fn adopt_windows(&mut self) {
+        let mut root = 0;
+        let mut parent = 0;
+        let mut children: *mut c_ulong = std::ptr::null_mut();
+        let mut count = 0;
+        if XQueryTree(self.display, self.root_window, &mut root, &mut parent, &mut children, &mut count) == 0 {
+            return;
+        }
+        let mut windows = Vec::new();
+        if !children.is_null() {
+            windows.extend_from_slice(std::slice::from_raw_parts(children, count as usize));
+            XFree(children as *mut _);
+        }
+        // Parents have to be managed before their transient windows
+        windows.sort_by_key(|window_id| {
+            let mut parent = 0;
+            XGetTransientForHint(self.display, *window_id, &mut parent) != 0
+        });
+
+        for window_id in windows {
+            if self.clients.contains_key(&window_id) || self.docks.contains_key(&window_id) {
+                continue;
+            }
+            let mut attributes: XWindowAttributes = std::mem::zeroed();
+            if XGetWindowAttributes(self.display, window_id, &mut attributes) == 0 || attributes.override_redirect != 0 || attributes.map_state != IsViewable {
+                continue;
+            }
+            let workspace = ewmh::get_longs(self.display, window_id, self.atoms.net_wm_desktop)
+                .first()
+                .map(|workspace| *workspace as usize)
+                .filter(|workspace| *workspace < self.workspaces.len());
+            self.manage_window(window_id, &attributes, workspace);
+        }
+    }
+
+    /// Returns the bounds of a new floating window of the given size, centered over its parent or, if it
+    /// has none, over the focused output.
+    unsafe fn centered_bounds(&self, attributes: &XWindowAttributes, parent: Option<c_ulong>) -> Bounds {
+        let area = match parent {
+            Some(parent) if self.is_visible(self.clients[&parent].workspace) => self.get_window_bounds(parent),
+            _ => self.work_area(self.focused_output),
+        };
+        let border_width = if let Some(border) = &self.config.border { border.width } else { 0 };
+        let screen = self.work_area(self.focused_output);
+        let width = (attributes.width + border_width * 2).clamp(MIN_FLOATING_SIZE, screen.width);
+        let height = (attributes.height + border_width * 2).clamp(MIN_FLOATING_SIZE, screen.height);
+        Bounds::new(area.x + (area.width - width) / 2, area.y + (area.height - height) / 2, width, height)
     }
 
     fn on_map_notify(&self, event: XMapEvent) {
@@ -215,16 +801,29 @@ impl WindowManager {
         }
     }
 
-    fn on_unmap_notify(&self, event: XUnmapEvent) {
+    unsafe fn on_unmap_notify(&mut self, event: XUnmapEvent) {
         if self.config.debug_events {
             println!("Unmap: {}", event.window);
         }
+
+        self.unmanage_dock(event.window);
+        if let Some(client) = self.clients.get_mut(&event.window) {
+            if client.pending_unmaps > 0 {
+                client.pending_unmaps -= 1;
+            } else {
+                ewmh::delete_property(self.display, event.window, self.atoms.net_wm_desktop);
+                self.unmanage_window(event.window);
+            }
+        }
     }
 
-    fn on_destroy_notify(&self, event: XDestroyWindowEvent) {
+    unsafe fn on_destroy_notify(&mut self, event: XDestroyWindowEvent) {
         if self.config.debug_events {
             println!("Destroy: {}", event.window);
         }
+
+        self.unmanage_dock(event.window);
+        self.unmanage_window(event.window);
     }
 
     fn on_reparent_notify(&self, event: XReparentEvent) {
@@ -245,61 +844,892 @@ impl WindowManager {
         }
     }
 
+    unsafe fn on_screen_change_notify(&mut self, event: &mut XEvent) {
+        if self.config.debug_events {
+            println!("Screen Change: {:?}", event);
+        }
+        if let Some(randr) = &self.randr {
+            randr.update_configuration(event);
+        }
+        self.update_outputs();
+    }
+
+    unsafe fn on_property_notify(&mut self, event: XPropertyEvent) {
+        if self.config.debug_events {
+            println!("Property: {{ window: {}, atom: {} }}", event.window, event.atom);
+        }
+
+        if event.atom == XA_WM_HINTS && self.clients.contains_key(&event.window) {
+            self.read_urgency_hint(event.window);
+            return;
+        }
+        if event.atom != self.atoms.net_wm_strut_partial && event.atom != self.atoms.net_wm_strut {
+            return;
+        }
+        if let Some(strut) = self.docks.get(&event.window) {
+            let new_strut = Strut::read(self.display, event.window, &self.atoms);
+            if *strut != new_strut {
+                self.docks.insert(event.window, new_strut);
+                self.update_work_areas();
+            }
+        }
+    }
+
+    /// Another window manager took the `WM_S<n>` selection to replace this one.
+    unsafe fn on_selection_clear(&mut self, event: XSelectionClearEvent) {
+        if self.config.debug_events {
+            println!("SelectionClear: {{ window: {}, selection: {} }}", event.window, event.selection);
+        }
+
+        if event.selection == self.atoms.wm_selection {
+            println!("Another window manager is taking over");
+            self.exit();
+        }
+    }
+
+    /// Handles EWMH requests from pagers, taskbars and the windows themselves.
+    unsafe fn on_client_message(&mut self, event: XClientMessageEvent) {
+        if self.config.debug_events {
+            println!("ClientMessage: {{ window: {}, message_type: {} }}", event.window, event.message_type);
+        }
+
+        let data = event.data.as_longs();
+        if event.message_type == self.atoms.net_current_desktop {
+            self.switch_workspace(data[0] as usize);
+            return;
+        }
+        if !self.clients.contains_key(&event.window) {
+            return;
+        }
+        if event.message_type == self.atoms.net_active_window {
+            self.focus_window(event.window);
+        } else if event.message_type == self.atoms.net_close_window {
+            self.close_window(event.window);
+        } else if event.message_type == self.atoms.net_wm_desktop {
+            self.move_window_to_workspace(event.window, data[0] as usize);
+        } else if event.message_type == self.atoms.net_wm_state {
+            for state in [data[1] as c_ulong, data[2] as c_ulong] {
+                if state == 0 {
+                    continue;
+                }
+                let enable = match data[0] {
+                    ewmh::STATE_REMOVE => false,
+                    ewmh::STATE_ADD => true,
+                    ewmh::STATE_TOGGLE => !self.has_state(event.window, state),
+                    _ => continue,
+                };
+                self.set_state(event.window, state, enable);
+            }
+        }
+    }
+
+    /// Starts moving (left button) or resizing (right button) the clicked window. Tiled windows are made
+    /// floating first.
+    unsafe fn on_button_press(&mut self, event: XButtonEvent) {
+        if self.config.debug_events {
+            println!("ButtonPress: {{ button: {}, window: {} }}", event.button, event.subwindow);
+        }
+
+        let window_id = event.subwindow;
+        let Some(client) = self.clients.get(&window_id).filter(|client| !client.fullscreen) else {
+            return;
+        };
+        if let Some(output) = self.outputs.iter().position(|output| output.workspace == client.workspace) {
+            self.focused_output = output;
+        }
+        self.float_window(window_id);
+        let Some(bounds) = self.clients.get(&window_id).and_then(|client| client.floating.clone()) else {
+            return;
+        };
+        self.focus_floating_window(window_id);
+        self.drag = Some(Drag {
+            window_id,
+            resize: event.button == Button3,
+            pointer_x: event.x_root,
+            pointer_y: event.y_root,
+            bounds,
+        });
+    }
+
+    fn on_button_release(&mut self, event: XButtonEvent) {
+        if self.config.debug_events {
+            println!("ButtonRelease: {{ button: {} }}", event.button);
+        }
+
+        self.drag = None;
+    }
+
+    unsafe fn on_motion_notify(&mut self, event: XMotionEvent) {
+        let Some(drag) = &self.drag else {
+            return;
+        };
+        let dx = event.x_root - drag.pointer_x;
+        let dy = event.y_root - drag.pointer_y;
+        let mut bounds = drag.bounds.clone();
+        if drag.resize {
+            bounds.width = (bounds.width + dx).max(MIN_FLOATING_SIZE);
+            bounds.height = (bounds.height + dy).max(MIN_FLOATING_SIZE);
+        } else {
+            bounds.x += dx;
+            bounds.y += dy;
+        }
+        let window_id = drag.window_id;
+        if let Some(client) = self.clients.get_mut(&window_id) {
+            client.floating = Some(bounds.clone());
+        }
+        self.configure_window(window_id, &bounds);
+    }
+
     fn on_keypress(&mut self, event: XKeyEvent) {
         if self.config.debug_events {
             println!("KeyPress: {{ keycode: {}, state: {} }}", event.keycode, event.state);
         }
         for keybind in &self.keybinds {
-            if event.keycode == keybind.keycode && event.state & (ShiftMask | ControlMask | Mod1Mask | Mod4Mask) == keybind.modifiers {
-                keybind.action.clone().execute(self); // TODO: probably find a better way to do this
+            if event.keycode == keybind.keycode && event.state & (ShiftMask | ControlMask | Mod1Mask | Mod3Mask | Mod4Mask | Mod5Mask) == keybind.modifiers {
+                if let Err(error) = keybind.action.clone().execute(self) { // TODO: probably find a better way to do this
+                    eprintln!("{}", error);
+                }
                 break;
             }
         }
     }
 
     pub unsafe fn move_focus(&mut self, direction: Direction) {
-        let window_id = self.layout.move_focus(direction);
+        let workspace = self.current_workspace();
+        if self.workspaces[workspace].focused_floating.take().is_some() {
+            self.focus_current_workspace();
+            return;
+        }
+        let window_id = self.layout().move_focus(direction.clone());
         if let Some(window_id) = window_id {
-            XSetInputFocus(self.display, window_id, RevertToNone, CurrentTime);
+            self.set_input_focus(window_id);
+        } else if let Some(output) = find_neighbour(&self.outputs, self.focused_output, direction) {
+            self.focused_output = output;
+            self.focus_current_workspace();
         }
     }
 
     unsafe fn configure_changed_windows(&mut self, changed: ChangedWindows) {
-        for (window_id, bounds) in changed {
-            let border_width = if let Some(border) = &self.config.border { border.width } else { 0 };
-            let border_space = (border_width * 2) as c_int;
+        for (window_id, bounds) in &changed {
+            self.configure_window(*window_id, bounds);
+        }
+        if !changed.is_empty() {
+            self.emit(Event::LayoutChanged { windows: changed });
+        }
+    }
 
-            let mut changes = XWindowChanges {
-                x: bounds.x,
-                y: bounds.y,
-                width: bounds.width - border_space,
-                height: bounds.height - border_space,
-                border_width,
-                sibling: 0,
-                stack_mode: 0,
-            };
-            XConfigureWindow(self.display, window_id, (CWX | CWY | CWWidth | CWHeight | CWBorderWidth) as c_uint, &mut changes);
+    /// Moves and resizes a window so that it fills `bounds`, including its border. Fullscreen windows
+    /// fill their output instead.
+    unsafe fn configure_window(&self, window_id: c_ulong, bounds: &Bounds) {
+        let client = self.clients.get(&window_id);
+        let configured_width = if let Some(border) = &self.config.border { border.width } else { 0 };
+        let mut border_width = client.and_then(|client| client.border_width).unwrap_or(configured_width);
+        let mut bounds = bounds;
+        let fullscreen_output = client
+            .filter(|client| client.fullscreen)
+            .and_then(|client| self.outputs.iter().find(|output| output.workspace == client.workspace));
+        if let Some(output) = fullscreen_output {
+            border_width = 0;
+            bounds = &output.bounds;
         }
+        let border_space = (border_width * 2) as c_int;
+
+        let mut changes = XWindowChanges {
+            x: bounds.x,
+            y: bounds.y,
+            width: bounds.width - border_space,
+            height: bounds.height - border_space,
+            border_width,
+            sibling: 0,
+            stack_mode: 0,
+        };
+        XConfigureWindow(self.display, window_id, (CWX | CWY | CWWidth | CWHeight | CWBorderWidth) as c_uint, &mut changes);
     }
 
+    /// Asks the focused window to close itself. The window is only removed from the layout once it is
+    /// actually unmapped or destroyed.
     pub unsafe fn close_focused_window(&mut self) {
-        if let Some((removed_window_id, new_focused_id, changed)) = self.layout.remove_focused_window() {
-            XKillClient(self.display, removed_window_id);
-            if let Some(new_focused_id) = new_focused_id {
-                XSetInputFocus(self.display, new_focused_id, RevertToNone, CurrentTime);
+        if let Some(window_id) = self.focused_window() {
+            self.close_window(window_id);
+        }
+    }
+
+    unsafe fn close_window(&mut self, window_id: c_ulong) {
+        if !self.supports_protocol(window_id, self.atoms.wm_delete_window) {
+            XKillClient(self.display, window_id);
+            return;
+        }
+
+        let mut event: XEvent = std::mem::zeroed();
+        event.client_message.type_ = ClientMessage;
+        event.client_message.window = window_id;
+        event.client_message.message_type = self.atoms.wm_protocols;
+        event.client_message.format = 32;
+        event.client_message.data.set_long(0, self.atoms.wm_delete_window as c_long);
+        event.client_message.data.set_long(1, CurrentTime as c_long);
+        XSendEvent(self.display, window_id, False, NoEventMask, &mut event);
+
+        if let (Some(client), Some(timeout)) = (self.clients.get_mut(&window_id), self.config.kill_timeout) {
+            client.kill_deadline.get_or_insert(Instant::now() + timeout);
+        }
+    }
+
+    /// Kills the clients that didn't react to WM_DELETE_WINDOW in time.
+    unsafe fn kill_unresponsive_clients(&mut self) {
+        let now = Instant::now();
+        for (window_id, client) in &mut self.clients {
+            if client.kill_deadline.is_some_and(|deadline| deadline <= now) {
+                client.kill_deadline = None;
+                XKillClient(self.display, *window_id);
             }
-            self.configure_changed_windows(changed);
         }
     }
 
+    unsafe fn supports_protocol(&self, window_id: c_ulong, protocol: c_ulong) -> bool {
+        let mut protocols = std::ptr::null_mut();
+        let mut count = 0;
+        if XGetWMProtocols(self.display, window_id, &mut protocols, &mut count) == 0 {
+            return false;
+        }
+        let supported = std::slice::from_raw_parts(protocols, count as usize).contains(&protocol);
+        XFree(protocols as *mut _);
+        supported
+    }
+
     pub fn change_tiling_direction(&mut self, direction: Direction) {
-        self.layout.change_tiling_direction(direction);
+        self.layout().change_tiling_direction(direction.clone());
+        let window = self.layout().focused_window();
+        self.emit(Event::TilingDirectionChanged { window, direction });
     }
 
     pub unsafe fn resize_focused_window(&mut self, direction: Direction, amount: f32) {
-        if let Some(changed) = self.layout.resize_focused_window(direction, amount) {
+        if let Some(changed) = self.layout().resize_focused_window(direction, amount) {
+            self.configure_changed_windows(changed);
+        }
+    }
+
+    pub unsafe fn change_master_count(&mut self, delta: i32) {
+        if let Some(changed) = self.layout().change_master_count(delta) {
+            self.configure_changed_windows(changed);
+        }
+    }
+
+    pub unsafe fn change_layout(&mut self, kind: LayoutKind) {
+        if self.layout().kind() == kind {
+            return;
+        }
+        let mut layout = create_layout(kind, self.outputs[self.focused_output].bounds.clone(), &self.config);
+        for window_id in self.layout().windows() {
+            layout.insert(Window::new(window_id));
+        }
+        let workspace = self.current_workspace();
+        self.workspaces[workspace].layout = layout;
+        self.arrange_output(self.focused_output);
+        self.focus_current_workspace();
+    }
+
+    pub unsafe fn change_gaps(&mut self, kind: GapKind, amount: i32, relative: bool) {
+        let gap = match kind {
+            GapKind::Inner => &mut self.config.gaps.inner,
+            GapKind::Outer => &mut self.config.gaps.outer,
+        };
+        *gap = if relative { gap.saturating_add(amount) } else { amount }.max(0);
+        for output in 0..self.outputs.len() {
+            self.arrange_output(output);
+        }
+    }
+
+    pub unsafe fn switch_workspace(&mut self, workspace: usize) {
+        if workspace >= self.workspaces.len() {
+            return;
+        }
+        if let Some(output) = self.outputs.iter().position(|output| output.workspace == workspace) {
+            if output != self.focused_output {
+                self.focused_output = output;
+                self.focus_current_workspace();
+            }
+            return;
+        }
+        self.hide_workspace(self.current_workspace());
+        self.outputs[self.focused_output].workspace = workspace;
+        self.show_workspace(self.focused_output);
+        self.focus_current_workspace();
+    }
+
+    pub unsafe fn move_focused_window_to_workspace(&mut self, workspace: usize) {
+        if let Some(window_id) = self.focused_window() {
+            self.move_window_to_workspace(window_id, workspace);
+        }
+    }
+
+    unsafe fn move_window_to_workspace(&mut self, window_id: c_ulong, workspace: usize) {
+        let Some(client) = self.clients.get(&window_id) else {
+            return;
+        };
+        if workspace == client.workspace || workspace >= self.workspaces.len() {
+            return;
+        }
+        let was_visible = self.is_visible(client.workspace);
+        self.detach_window(window_id);
+        self.attach_window(window_id, workspace);
+        if !self.is_visible(workspace) {
+            if was_visible {
+                self.unmap_window(window_id);
+            }
+        } else if !was_visible {
+            XMapWindow(self.display, window_id);
+        }
+        self.focus_current_workspace();
+    }
+
+    /// Shows the workspace of a window and focuses it, like clicking on its entry in a taskbar.
+    unsafe fn focus_window(&mut self, window_id: c_ulong) {
+        let Some(client) = self.clients.get(&window_id) else {
+            return;
+        };
+        let workspace = client.workspace;
+        let floating = client.floating.is_some();
+        self.switch_workspace(workspace);
+        if floating {
+            self.focus_floating_window(window_id);
+        } else {
+            self.workspaces[workspace].focused_floating = None;
+            self.workspaces[workspace].layout.focus_window(window_id);
+            self.focus_current_workspace();
+        }
+    }
+
+    pub unsafe fn toggle_fullscreen(&mut self) {
+        if let Some(window_id) = self.focused_window() {
+            let fullscreen = self.has_state(window_id, self.atoms.net_wm_state_fullscreen);
+            self.set_state(window_id, self.atoms.net_wm_state_fullscreen, !fullscreen);
+        }
+    }
+
+    /// Lets a window cover its whole output or puts it back where it was before.
+    unsafe fn set_fullscreen(&mut self, window_id: c_ulong, fullscreen: bool) {
+        let Some(client) = self.clients.get_mut(&window_id) else {
+            return;
+        };
+        if client.fullscreen == fullscreen {
+            return;
+        }
+        client.fullscreen = fullscreen;
+        let workspace = client.workspace;
+        if let Some(bounds) = client.floating.clone() {
+            self.configure_window(window_id, &bounds);
+        } else if self.is_visible(workspace) {
+            let changed = self.workspaces[workspace].layout.arrange();
+            self.configure_changed_windows(changed);
+        }
+        self.raise_floating_windows(workspace);
+    }
+
+    fn has_state(&self, window_id: c_ulong, state: c_ulong) -> bool {
+        let Some(client) = self.clients.get(&window_id) else {
+            return false;
+        };
+        if state == self.atoms.net_wm_state_fullscreen {
+            client.fullscreen
+        } else if state == self.atoms.net_wm_state_demands_attention {
+            client.urgent
+        } else {
+            false
+        }
+    }
+
+    /// Turns a `_NET_WM_STATE` on or off. Unsupported states are ignored.
+    unsafe fn set_state(&mut self, window_id: c_ulong, state: c_ulong, enable: bool) {
+        let Some(client) = self.clients.get_mut(&window_id) else {
+            return;
+        };
+        if state == self.atoms.net_wm_state_fullscreen {
+            self.set_fullscreen(window_id, enable);
+        } else if state == self.atoms.net_wm_state_demands_attention {
+            client.urgent = enable && window_id != self.input_focus;
+            self.update_border(window_id);
+        }
+        self.update_state(window_id);
+    }
+
+    /// Marks a window as urgent if the urgency hint in its `WM_HINTS` is set, and clears the mark if it
+    /// isn't. The focused window is never urgent.
+    unsafe fn read_urgency_hint(&mut self, window_id: c_ulong) {
+        let hints = XGetWMHints(self.display, window_id);
+        let urgent = !hints.is_null() && (*hints).flags & XUrgencyHint != 0;
+        if !hints.is_null() {
+            XFree(hints as *mut _);
+        }
+        let Some(client) = self.clients.get_mut(&window_id) else {
+            return;
+        };
+        let urgent = urgent && window_id != self.input_focus;
+        if client.urgent != urgent {
+            client.urgent = urgent;
+            self.update_border(window_id);
+            self.update_state(window_id);
+        }
+    }
+
+    /// Writes the states of a window to its `_NET_WM_STATE` property.
+    unsafe fn update_state(&self, window_id: c_ulong) {
+        let Some(client) = self.clients.get(&window_id) else {
+            return;
+        };
+        let mut states = Vec::new();
+        if client.fullscreen {
+            states.push(self.atoms.net_wm_state_fullscreen);
+        }
+        if client.urgent {
+            states.push(self.atoms.net_wm_state_demands_attention);
+        }
+        ewmh::set_atoms(self.display, window_id, self.atoms.net_wm_state, &states);
+    }
+
+    pub unsafe fn move_focused_window_to_output(&mut self, direction: Direction) {
+        let Some(output) = find_neighbour(&self.outputs, self.focused_output, direction) else {
+            return;
+        };
+        if let Some(window_id) = self.focused_window() {
+            self.detach_window(window_id);
+            if let Some(Some(bounds)) = self.clients.get_mut(&window_id).map(|client| &mut client.floating) {
+                let from = &self.outputs[self.focused_output].bounds;
+                let to = &self.outputs[output].bounds;
+                bounds.x += to.x - from.x;
+                bounds.y += to.y - from.y;
+                let bounds = bounds.clone();
+                self.configure_window(window_id, &bounds);
+            }
+            self.attach_window(window_id, self.outputs[output].workspace);
+            self.focused_output = output;
+            self.set_input_focus(window_id);
+        }
+    }
+
+    pub unsafe fn toggle_floating(&mut self) {
+        let Some(window_id) = self.focused_window() else {
+            return;
+        };
+        if self.clients.get(&window_id).is_some_and(|client| client.floating.is_some()) {
+            self.tile_window(window_id);
+        } else {
+            self.float_window(window_id);
+            self.focus_floating_window(window_id);
+        }
+    }
+
+    /// Takes a tiled window out of its layout and lets it float at its current position.
+    unsafe fn float_window(&mut self, window_id: c_ulong) {
+        if self.clients.get(&window_id).is_none_or(|client| client.floating.is_some()) {
+            return;
+        }
+        let bounds = self.get_window_bounds(window_id);
+        self.detach_window(window_id);
+        let client = self.clients.get_mut(&window_id).unwrap();
+        client.floating = Some(bounds.clone());
+        let workspace = client.workspace;
+        self.attach_window(window_id, workspace);
+        self.configure_window(window_id, &bounds);
+    }
+
+    /// Puts a floating window back into the layout of its workspace, next to the focused window.
+    unsafe fn tile_window(&mut self, window_id: c_ulong) {
+        if self.clients.get(&window_id).is_none_or(|client| client.floating.is_none()) {
+            return;
+        }
+        self.detach_window(window_id);
+        let client = self.clients.get_mut(&window_id).unwrap();
+        client.floating = None;
+        let workspace = client.workspace;
+        self.attach_window(window_id, workspace);
+        self.raise_floating_windows(workspace);
+        self.set_input_focus(window_id);
+    }
+
+    unsafe fn focus_floating_window(&mut self, window_id: c_ulong) {
+        if let Some(client) = self.clients.get(&window_id) {
+            self.workspaces[client.workspace].focused_floating = Some(window_id);
+            XRaiseWindow(self.display, window_id);
+            self.set_input_focus(window_id);
+        }
+    }
+
+    /// Takes a window out of its workspace without unmanaging it.
+    unsafe fn detach_window(&mut self, window_id: c_ulong) {
+        let Some(client) = self.clients.get(&window_id) else {
+            return;
+        };
+        let workspace = client.workspace;
+        if client.floating.is_some() {
+            let workspace = &mut self.workspaces[workspace];
+            workspace.floating.retain(|floating_id| *floating_id != window_id);
+            if workspace.focused_floating == Some(window_id) {
+                workspace.focused_floating = None;
+            }
+        } else if let Some(changed) = self.workspaces[workspace].layout.remove_window(window_id) {
+            if self.is_visible(workspace) {
+                self.configure_changed_windows(changed);
+            }
+        }
+    }
+
+    unsafe fn attach_window(&mut self, window_id: c_ulong, workspace: usize) {
+        let Some(client) = self.clients.get_mut(&window_id) else {
+            return;
+        };
+        client.workspace = workspace;
+        ewmh::set_cardinal(self.display, window_id, self.atoms.net_wm_desktop, workspace as c_ulong);
+        if client.floating.is_some() {
+            self.workspaces[workspace].floating.push(window_id);
+            self.workspaces[workspace].focused_floating = Some(window_id);
+        } else {
+            self.workspaces[workspace].focused_floating = None;
+            let changed = self.workspaces[workspace].layout.insert(Window::new(window_id));
+            if self.is_visible(workspace) {
+                self.configure_changed_windows(changed);
+            }
+        }
+        self.update_client_list();
+    }
+
+    /// Puts a new tiled window into a placeholder of a loaded layout that matches it. Only placeholders on
+    /// `workspace` are considered if it is set, otherwise those on the focused workspace are preferred.
+    /// Returns the workspace of the placeholder.
+    unsafe fn swallow_window(&mut self, window_id: c_ulong, properties: &WindowProperties, workspace: Option<usize>) -> Option<usize> {
+        let matches = |criteria: &Criteria| criteria.matches(properties);
+        let preferred = workspace.unwrap_or_else(|| self.current_workspace());
+        let others = (0..self.workspaces.len()).filter(|other| workspace.is_none() && *other != preferred);
+        let (workspace, changed) = std::iter::once(preferred)
+            .chain(others)
+            .find_map(|workspace| Some((workspace, self.workspaces[workspace].layout.swallow(Window::new(window_id), &matches)?)))?;
+        self.clients.get_mut(&window_id)?.workspace = workspace;
+        ewmh::set_cardinal(self.display, window_id, self.atoms.net_wm_desktop, workspace as c_ulong);
+        self.workspaces[workspace].focused_floating = None;
+        if self.is_visible(workspace) {
             self.configure_changed_windows(changed);
         }
+        self.update_client_list();
+        Some(workspace)
+    }
+
+    /// Writes the layout of the focused workspace to a layout file. Windows are described by their
+    /// class and instance, or their title if they have no class. The file can be edited to match them
+    /// differently.
+    pub unsafe fn save_layout(&mut self, path: &Path) -> Result<(), String> {
+        let criteria = |window_id| Criteria::from_properties(&WindowProperties::read(self.display, window_id, &self.atoms));
+        let contents = self.workspaces[self.current_workspace()].layout.export(&criteria)
+            .ok_or("only tree layouts with windows that have a class or title can be saved")?;
+        std::fs::write(path, contents + "\n").map_err(|error| error.to_string())
+    }
+
+    /// Replaces the layout of the focused workspace with the one from a layout file. Its windows fill
+    /// the placeholders they match, the rest are inserted as usual.
+    pub unsafe fn load_layout(&mut self, path: &Path) -> Result<(), String> {
+        let contents = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        let file: Value = serde_json::from_str(&contents).map_err(|error| error.to_string())?;
+        let tree = WindowTree::load(&file, self.work_area(self.focused_output))?;
+        let windows = self.layout().windows();
+        let workspace = self.current_workspace();
+        self.workspaces[workspace].layout = Box::new(tree);
+        for window_id in windows {
+            let properties = WindowProperties::read(self.display, window_id, &self.atoms);
+            if self.layout().swallow(Window::new(window_id), &|criteria| criteria.matches(&properties)).is_none() {
+                self.layout().insert(Window::new(window_id));
+            }
+        }
+        self.arrange_output(self.focused_output);
+        self.focus_current_workspace();
+        Ok(())
+    }
+
+    /// Re-detects the outputs after the screen configuration changed. Outputs that are still present keep
+    /// their workspace, new outputs get the first workspace that isn't visible yet.
+    unsafe fn update_outputs(&mut self) {
+        let detected = detect_outputs(self.display, self.root_window, self.randr.as_ref(), &self.config.monitors);
+        let old_outputs = std::mem::take(&mut self.outputs);
+        let focused_name = old_outputs.get(self.focused_output).map(|output| output.name.clone());
+
+        let mut outputs: Vec<(String, Bounds, Option<usize>)> = detected.into_iter()
+            .map(|(name, bounds)| {
+                let workspace = old_outputs.iter().find(|output| output.name == name).map(|output| output.workspace);
+                (name, bounds, workspace)
+            })
+            .collect();
+        for index in 0..outputs.len() {
+            if outputs[index].2.is_none() {
+                let free = (0..self.workspaces.len()).find(|workspace| !outputs.iter().any(|output| output.2 == Some(*workspace)));
+                let workspace = free.unwrap_or_else(|| {
+                    self.workspaces.push(Workspace::new(create_layout(self.config.layout.clone(), outputs[index].1.clone(), &self.config)));
+                    self.workspaces.len() - 1
+                });
+                outputs[index].2 = Some(workspace);
+            }
+        }
+        self.outputs = outputs.into_iter()
+            .map(|(name, bounds, workspace)| Output::new(name, bounds, workspace.unwrap()))
+            .collect();
+        println!("Outputs: {:?}", self.outputs);
+
+        for old_output in &old_outputs {
+            if !self.outputs.iter().any(|output| output.workspace == old_output.workspace) {
+                self.hide_workspace(old_output.workspace);
+            }
+        }
+        self.focused_output = self.outputs.iter()
+            .position(|output| Some(&output.name) == focused_name.as_ref())
+            .unwrap_or(0);
+        for output in 0..self.outputs.len() {
+            self.show_workspace(output);
+        }
+        self.update_desktops();
+        self.focus_current_workspace();
+    }
+
+    unsafe fn update_desktops(&self) {
+        ewmh::set_cardinal(self.display, self.root_window, self.atoms.net_number_of_desktops, self.workspaces.len() as c_ulong);
+    }
+
+    /// Publishes the managed windows in mapping order and, per workspace, from the bottom to the top.
+    unsafe fn update_client_list(&self) {
+        ewmh::set_windows(self.display, self.root_window, self.atoms.net_client_list, &self.client_list);
+        let stacking: Vec<c_ulong> = self.workspaces.iter().flat_map(Workspace::windows).collect();
+        ewmh::set_windows(self.display, self.root_window, self.atoms.net_client_list_stacking, &stacking);
+    }
+
+    unsafe fn show_workspace(&mut self, output: usize) {
+        let workspace = self.outputs[output].workspace;
+        self.arrange_output(output);
+        for window_id in self.workspaces[workspace].windows() {
+            XMapWindow(self.display, window_id);
+        }
+        self.raise_floating_windows(workspace);
+    }
+
+    /// Lays out the workspace of an output in the space that isn't reserved by docks.
+    unsafe fn arrange_output(&mut self, output: usize) {
+        let bounds = self.work_area(output);
+        let layout = self.workspaces[self.outputs[output].workspace].layout.as_mut();
+        layout.set_bounds(bounds);
+        layout.set_gaps(self.config.gaps);
+        let changed = layout.arrange();
+        self.configure_changed_windows(changed);
+    }
+
+    unsafe fn work_area(&self, output: usize) -> Bounds {
+        let screen = XDefaultScreen(self.display);
+        let screen_width = XDisplayWidth(self.display, screen);
+        let screen_height = XDisplayHeight(self.display, screen);
+        self.docks.values()
+            .flatten()
+            .fold(self.outputs[output].bounds.clone(), |bounds, strut| strut.apply(&bounds, screen_width, screen_height))
+    }
+
+    unsafe fn update_work_areas(&mut self) {
+        for output in 0..self.outputs.len() {
+            self.arrange_output(output);
+        }
+    }
+
+    /// Maps a dock where it wants to be and makes room for it.
+    unsafe fn manage_dock(&mut self, window_id: c_ulong) {
+        XSelectInput(self.display, window_id, PropertyChangeMask);
+        let strut = Strut::read(self.display, window_id, &self.atoms);
+        self.docks.insert(window_id, strut);
+        XMapWindow(self.display, window_id);
+        XRaiseWindow(self.display, window_id);
+        self.update_work_areas();
+    }
+
+    unsafe fn unmanage_dock(&mut self, window_id: c_ulong) {
+        if self.docks.remove(&window_id).is_some() {
+            self.update_work_areas();
+        }
+    }
+
+    unsafe fn hide_workspace(&mut self, workspace: usize) {
+        for window_id in self.workspaces[workspace].windows() {
+            self.unmap_window(window_id);
+        }
+    }
+
+    /// Raises the floating windows of a workspace above the tiled ones, and fullscreen windows above both.
+    unsafe fn raise_floating_windows(&self, workspace: usize) {
+        for window_id in &self.workspaces[workspace].floating {
+            XRaiseWindow(self.display, *window_id);
+        }
+        for window_id in self.workspaces[workspace].windows() {
+            if self.clients.get(&window_id).is_some_and(|client| client.fullscreen) {
+                XRaiseWindow(self.display, window_id);
+            }
+        }
+    }
+
+    /// Unmaps a window and remembers that the resulting UnmapNotify doesn't mean the client withdrew it.
+    unsafe fn unmap_window(&mut self, window_id: c_ulong) {
+        if let Some(client) = self.clients.get_mut(&window_id) {
+            client.pending_unmaps += 1;
+        }
+        XUnmapWindow(self.display, window_id);
+    }
+
+    /// Stops managing the windows that caused BadWindow or BadMatch errors and no longer exist.
+    unsafe fn drop_failed_windows(&mut self) {
+        let failed = std::mem::take(&mut *FAILED_WINDOWS.lock().unwrap());
+        for window_id in failed {
+            if !self.clients.contains_key(&window_id) && !self.docks.contains_key(&window_id) {
+                continue;
+            }
+            let mut attributes: XWindowAttributes = std::mem::zeroed();
+            if XGetWindowAttributes(self.display, window_id, &mut attributes) != 0 {
+                continue;
+            }
+            if self.config.debug_events {
+                println!("Dropping destroyed window {}", window_id);
+            }
+            self.unmanage_dock(window_id);
+            self.unmanage_window(window_id);
+        }
+    }
+
+    /// Forgets about a window that was withdrawn or destroyed by its client and re-flows its workspace.
+    unsafe fn unmanage_window(&mut self, window_id: c_ulong) {
+        let Some(workspace) = self.clients.get(&window_id).map(|client| client.workspace) else {
+            return;
+        };
+        self.detach_window(window_id);
+        self.clients.remove(&window_id);
+        self.client_list.retain(|client_id| *client_id != window_id);
+        self.update_client_list();
+        self.emit(Event::WindowUnmanaged { window: window_id });
+        if self.drag.as_ref().is_some_and(|drag| drag.window_id == window_id) {
+            self.drag = None;
+        }
+        if workspace == self.current_workspace() {
+            self.focus_current_workspace();
+        }
+    }
+
+    unsafe fn focus_current_workspace(&mut self) {
+        let window_id = self.focused_window().unwrap_or(self.root_window);
+        self.set_input_focus(window_id);
+    }
+
+    /// Gives the input focus to a window, or to nothing if `window_id` is the root window.
+    unsafe fn set_input_focus(&mut self, window_id: c_ulong) {
+        XSetInputFocus(self.display, window_id, RevertToNone, CurrentTime);
+        let active_window = if window_id == self.root_window { 0 } else { window_id };
+        ewmh::set_window(self.display, self.root_window, self.atoms.net_active_window, active_window);
+        ewmh::set_cardinal(self.display, self.root_window, self.atoms.net_current_desktop, self.current_workspace() as c_ulong);
+        if window_id != self.input_focus {
+            let previous_focus = std::mem::replace(&mut self.input_focus, window_id);
+            if let Some(client) = self.clients.get_mut(&window_id).filter(|client| client.urgent) {
+                client.urgent = false;
+                self.update_state(window_id);
+            }
+            self.update_border(previous_focus);
+            self.update_border(window_id);
+            let window = Some(window_id).filter(|window_id| *window_id != self.root_window);
+            self.emit(Event::FocusChanged { window });
+        }
+    }
+
+    fn focused_window(&self) -> Option<c_ulong> {
+        let workspace = &self.workspaces[self.current_workspace()];
+        workspace.focused_floating.or_else(|| workspace.layout.focused_window())
+    }
+
+    fn is_visible(&self, workspace: usize) -> bool {
+        self.outputs.iter().any(|output| output.workspace == workspace)
+    }
+
+    /// Returns the geometry of a window including its border.
+    unsafe fn get_window_bounds(&self, window_id: c_ulong) -> Bounds {
+        let mut attributes: XWindowAttributes = std::mem::zeroed();
+        XGetWindowAttributes(self.display, window_id, &mut attributes);
+        let border_space = attributes.border_width * 2;
+        Bounds::new(attributes.x, attributes.y, attributes.width + border_space, attributes.height + border_space)
+    }
+
+    fn current_workspace(&self) -> usize {
+        self.outputs[self.focused_output].workspace
+    }
+
+    fn layout(&mut self) -> &mut dyn Layout {
+        let workspace = self.current_workspace();
+        self.workspaces[workspace].layout.as_mut()
+    }
+
+    /// Re-reads the config file. If it is invalid, the current config stays active.
+    pub unsafe fn reload_config(&mut self) -> Result<(), String> {
+        let config = Config::load().map_err(|error| format!("Couldn't reload config, keeping the current one: {}", error))?;
+        if config.debug_events {
+            println!("Config: {:?}", config);
+        }
+
+        self.ungrab_keybinds();
+        if let Some(border_colors) = self.border_colors.take() {
+            border_colors.free(self.display);
+        }
+        self.border_colors = config.border.as_ref().map(|border| BorderColors::allocate(self.display, border));
+        self.config = config;
+        self.register_keybinds();
+        while self.workspaces.len() < self.config.workspace_count {
+            let layout = create_layout(self.config.layout.clone(), self.outputs[0].bounds.clone(), &self.config);
+            self.workspaces.push(Workspace::new(layout));
+        }
+        self.update_outputs();
+        self.apply_borders();
+        self.emit(Event::ConfigReloaded);
+        Ok(())
+    }
+
+    /// Paints the border of a window in the color for its focus and urgency.
+    unsafe fn update_border(&self, window_id: c_ulong) {
+        let (Some(colors), Some(client)) = (&self.border_colors, self.clients.get(&window_id)) else {
+            return;
+        };
+        let pixel = if client.urgent {
+            colors.urgent
+        } else if window_id == self.input_focus {
+            colors.focused
+        } else {
+            colors.unfocused
+        };
+        XSetWindowBorder(self.display, window_id, pixel);
+    }
+
+    /// Applies the configured border width and colors to every managed window.
+    unsafe fn apply_borders(&mut self) {
+        for output in 0..self.outputs.len() {
+            self.arrange_output(output);
+        }
+        for (window_id, client) in &self.clients {
+            self.update_border(*window_id);
+            if let Some(bounds) = &client.floating {
+                self.configure_window(*window_id, bounds);
+            }
+        }
+    }
+
+    unsafe fn register_keybinds(&mut self) {
+        for (combination, action) in self.config.keybinds.clone() {
+            self.register_keybind(combination.keysym, combination.modifiers, action);
+        }
+    }
+
+    unsafe fn ungrab_keybinds(&mut self) {
+        for keybind in self.keybinds.drain(..) {
+            for modifiers in [keybind.modifiers, keybind.modifiers | Mod2Mask, keybind.modifiers | LockMask] {
+                XUngrabKey(self.display, keybind.keycode as c_int, modifiers, self.root_window);
+            }
+        }
+    }
+
+    unsafe fn grab_button(&self, button: c_uint, modifiers: c_uint) {
+        let event_mask = (ButtonPressMask | ButtonReleaseMask | PointerMotionMask) as c_uint;
+        for modifiers in [modifiers, modifiers | Mod2Mask, modifiers | LockMask] {
+            XGrabButton(self.display, button, modifiers, self.root_window, False, event_mask, GrabModeAsync, GrabModeAsync, 0, 0);
+        }
     }
 
     unsafe fn register_keybind(&mut self, key: c_uint, modifiers: c_uint, action: Action) {
@@ -310,11 +1740,183 @@ impl WindowManager {
         self.keybinds.push(Keybind::new(keycode as c_uint, modifiers, action));
     }
 
-    pub unsafe fn close_connection(&self) {
-        XCloseDisplay(self.display);
+    /// Makes the event loop stop after the current event.
+    pub fn exit(&mut self) {
+        self.running = false;
+    }
+
+    /// Saves the state and leaves the event loop to replace the process with a new instance. Nothing
+    /// happens if the state can't be saved.
+    pub fn restart(&mut self) -> Result<(), String> {
+        let path = RestartState::path().map_err(|error| format!("Couldn't restart: {}", error))?;
+        self.save_state().save(&path).map_err(|error| format!("Couldn't restart: {}", error))?;
+        self.restart_state = Some(path);
+        self.exit();
+        Ok(())
     }
 
-    pub unsafe fn exit(&self) {
-        self.close_connection();
+    /// Hands the display over to a new instance that restores the state from `path`. The windows stay
+    /// exactly as they are. Only returns if the new instance couldn't be started, after showing every
+    /// window like on a normal exit.
+    unsafe fn exec_restart(&mut self, path: &Path) -> ExitCode {
+        self.ipc = None;
+        XSync(self.display, False);
+        // The connection is only closed by a successful exec, so that the windows on hidden workspaces
+        // can still be mapped if it fails
+        let connection = XConnectionNumber(self.display);
+        libc::fcntl(connection, libc::F_SETFD, libc::fcntl(connection, libc::F_GETFD) | libc::FD_CLOEXEC);
+        let program = std::env::args().next().unwrap_or_else(|| "xnebula".to_string());
+        // The server may not have released the selection of the closed connection yet
+        let error = std::process::Command::new(&program).arg("--replace").arg("--restore").arg(path).exec();
+        eprintln!("Couldn't restart {}: {}", program, error);
+        let _ = std::fs::remove_file(path);
+        self.shutdown();
+        ExitCode::FAILURE
+    }
+
+    fn save_state(&self) -> RestartState {
+        let workspaces = self.workspaces.iter()
+            .map(|workspace| WorkspaceState {
+                layout: workspace.layout.save(),
+                floating: workspace.floating.clone(),
+                focused_floating: workspace.focused_floating,
+            })
+            .collect();
+        let clients = self.client_list.iter()
+            .map(|window_id| {
+                let client = &self.clients[window_id];
+                ClientState {
+                    window: *window_id,
+                    workspace: client.workspace,
+                    original_border_width: client.original_border_width,
+                    border_width: client.border_width,
+                    floating: client.floating.clone(),
+                    fullscreen: client.fullscreen,
+                }
+            })
+            .collect();
+        RestartState {
+            workspaces,
+            outputs: self.outputs.iter().map(|output| (output.name.clone(), output.workspace)).collect(),
+            focused_output: self.outputs.get(self.focused_output).map(|output| output.name.clone()),
+            clients,
+        }
+    }
+
+    /// Takes over the windows of the instance that restarted into this one, with the exact same layouts.
+    /// Windows that disappeared in the meantime are left out.
+    pub unsafe fn restore(&mut self, path: &Path) {
+        let state = match RestartState::load(path) {
+            Ok(state) => state,
+            Err(error) => {
+                eprintln!("Couldn't restore the previous state: {}", error);
+                return;
+            }
+        };
+
+        for client_state in state.clients {
+            let mut attributes: XWindowAttributes = std::mem::zeroed();
+            if XGetWindowAttributes(self.display, client_state.window, &mut attributes) == 0 || attributes.override_redirect != 0 {
+                continue;
+            }
+            if client_state.workspace >= state.workspaces.len() || self.clients.contains_key(&client_state.window) {
+                continue;
+            }
+            let mut client = Client::new(client_state.workspace);
+            client.original_border_width = client_state.original_border_width;
+            client.border_width = client_state.border_width;
+            client.floating = client_state.floating;
+            client.fullscreen = client_state.fullscreen;
+            self.add_client(client_state.window, client);
+        }
+
+        while self.workspaces.len() < state.workspaces.len() {
+            self.workspaces.push(Workspace::new(create_layout(self.config.layout.clone(), self.outputs[0].bounds.clone(), &self.config)));
+        }
+        for (index, saved) in state.workspaces.into_iter().enumerate() {
+            let is_tiled_here = |window_id: c_ulong| self.clients.get(&window_id).is_some_and(|client| client.workspace == index && client.floating.is_none());
+            let bounds = self.outputs[0].bounds.clone();
+            let layout = restore_layout(saved.layout, bounds.clone(), &is_tiled_here);
+            let layout = layout.unwrap_or_else(|| {
+                // Fall back to a fresh layout so that no window gets lost
+                let mut layout = create_layout(self.config.layout.clone(), bounds, &self.config);
+                for window_id in &self.client_list {
+                    if is_tiled_here(*window_id) {
+                        layout.insert(Window::new(*window_id));
+                    }
+                }
+                layout
+            });
+            let is_floating_here = |window_id: &c_ulong| self.clients.get(window_id).is_some_and(|client| client.workspace == index && client.floating.is_some());
+            let mut workspace = Workspace::new(layout);
+            workspace.floating = saved.floating.into_iter().filter(is_floating_here).collect();
+            workspace.focused_floating = saved.focused_floating.filter(is_floating_here);
+            self.workspaces[index] = workspace;
+        }
+
+        let mut used = Vec::new();
+        for output in &mut self.outputs {
+            if let Some((_, workspace)) = state.outputs.iter().find(|(name, workspace)| *name == output.name && !used.contains(workspace) && *workspace < self.workspaces.len()) {
+                output.workspace = *workspace;
+            }
+            used.push(output.workspace);
+        }
+        if let Some(focused_output) = self.outputs.iter().position(|output| Some(&output.name) == state.focused_output.as_ref()) {
+            self.focused_output = focused_output;
+        }
+
+        for window_id in self.client_list.clone() {
+            let workspace = self.clients[&window_id].workspace;
+            ewmh::set_cardinal(self.display, window_id, self.atoms.net_wm_desktop, workspace as c_ulong);
+            self.update_state(window_id);
+            if !self.is_visible(workspace) {
+                let mut attributes: XWindowAttributes = std::mem::zeroed();
+                if XGetWindowAttributes(self.display, window_id, &mut attributes) != 0 && attributes.map_state == IsViewable {
+                    self.unmap_window(window_id);
+                }
+            }
+        }
+        for output in 0..self.outputs.len() {
+            self.show_workspace(output);
+        }
+        self.apply_borders();
+    }
+
+    /// Releases the grabs and leaves every window mapped, with its original border and on screen, so
+    /// that the session stays usable without a window manager or with the next one.
+    unsafe fn shutdown(&mut self) {
+        self.ungrab_keybinds();
+        XUngrabButton(self.display, Button1, AnyModifier, self.root_window);
+        XUngrabButton(self.display, Button3, AnyModifier, self.root_window);
+
+        let screen = XDefaultScreen(self.display);
+        let screen_width = XDisplayWidth(self.display, screen);
+        let screen_height = XDisplayHeight(self.display, screen);
+        for (window_id, client) in &self.clients {
+            let mut attributes: XWindowAttributes = std::mem::zeroed();
+            if XGetWindowAttributes(self.display, *window_id, &mut attributes) == 0 {
+                continue;
+            }
+            let mut changes = XWindowChanges {
+                x: attributes.x.clamp(0, (screen_width - attributes.width).max(0)),
+                y: attributes.y.clamp(0, (screen_height - attributes.height).max(0)),
+                width: 0,
+                height: 0,
+                border_width: client.original_border_width,
+                sibling: 0,
+                stack_mode: 0,
+            };
+            XConfigureWindow(self.display, *window_id, (CWX | CWY | CWBorderWidth) as c_uint, &mut changes);
+            XMapWindow(self.display, *window_id);
+        }
+
+        XSetInputFocus(self.display, PointerRoot as c_ulong, RevertToPointerRoot, CurrentTime);
+        XDeleteProperty(self.display, self.root_window, self.atoms.net_supporting_wm_check);
+        XDeleteProperty(self.display, self.root_window, self.atoms.net_supported);
+        XDeleteProperty(self.display, self.root_window, self.atoms.net_active_window);
+        XDestroyWindow(self.display, self.check_window);
+        XSync(self.display, False);
+        XCloseDisplay(self.display);
+        self.ipc = None;
     }
 }
@@ -0,0 +1,25 @@
+use std::ffi::c_ulong;
+use crate::layout::Layout;
+
+pub struct Workspace {
+    pub layout: Box<dyn Layout>,
+    /// Floating windows, from bottom to top.
+    pub floating: Vec<c_ulong>,
+    pub focused_floating: Option<c_ulong>,
+}
+
+impl Workspace {
+    pub fn new(layout: Box<dyn Layout>) -> Self {
+        Self {
+            layout,
+            floating: Vec::new(),
+            focused_floating: None,
+        }
+    }
+
+    pub fn windows(&self) -> Vec<c_ulong> {
+        let mut windows = self.layout.windows();
+        windows.extend(&self.floating);
+        windows
+    }
+}